@@ -0,0 +1,31 @@
+use std::{
+    process::Command,
+    time::{Duration, Instant},
+};
+
+const RUNS: u32 = 5;
+const SCRIPTS: [&str; 2] = ["./benches/loops.rlox", "./tests/loops/loops.rlox"];
+const BACKENDS: [&str; 2] = ["tree", "vm"];
+
+fn run(script: &str, backend: &str) -> Duration {
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(format!("--backend={backend}"))
+        .arg(script)
+        .output()
+        .expect("failed to run rlox")
+        .status;
+    assert!(status.success(), "{script} failed");
+    start.elapsed()
+}
+
+fn main() {
+    for script in SCRIPTS {
+        for backend in BACKENDS {
+            let times: Vec<Duration> = (0..RUNS).map(|_| run(script, backend)).collect();
+            let best = times.iter().min().unwrap();
+            let mean = times.iter().sum::<Duration>() / RUNS;
+            println!("{script} ({backend}): best {best:?}, mean {mean:?} over {RUNS} runs");
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use std::usize;
+use crate::token::{LiteralValue, Token};
 
-use crate::token::*;
+use super::stmt::FnStmt;
 
-type ExprId = usize;
+pub type ExprId = usize;
 
 macro_rules! parenthize_expr {
     ($name:expr, $($exprs:expr),*) => {
@@ -33,6 +33,13 @@ pub enum Expr {
     Get(Get),
     Set(Set),
     This(This),
+    Super(Super),
+    List(List),
+    Map(Map),
+    Index(Index),
+    SetIndex(SetIndex),
+    Lambda(Lambda),
+    Interpolation(Interpolation),
 }
 
 #[derive(Debug, Clone)]
@@ -80,7 +87,7 @@ pub struct Logical {
 
 #[derive(Debug, Clone)]
 pub struct Call {
-    pub callee: String,
+    pub callee: Box<Expr>,
     pub paren: Token,
     pub args: Vec<Expr>,
 }
@@ -89,7 +96,7 @@ pub struct Call {
 pub struct Get {
     pub object: Box<Expr>,
     pub name: Token,
-    pub method_args: Option<Vec<Expr>>
+    pub method_args: Option<Vec<Expr>>,
 }
 
 #[derive(Debug, Clone)]
@@ -102,11 +109,61 @@ pub struct Set {
 #[derive(Debug, Clone)]
 pub struct This {
     pub keyword: Token,
+    pub id: ExprId,
 }
 
-impl Expr {
-    pub fn to_string(&self) -> String {
-        match self {
+#[derive(Debug, Clone)]
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+    pub method_args: Option<Vec<Expr>>,
+    pub id: ExprId,
+}
+
+#[derive(Debug, Clone)]
+pub struct List {
+    pub bracket: Token,
+    pub elements: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Map {
+    pub brace: Token,
+    pub entries: Vec<(Expr, Expr)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub key: Box<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetIndex {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub key: Box<Expr>,
+    pub value: Box<Expr>,
+}
+
+/// An anonymous function, `fun (a) { ... }` or `(a) => ...`.
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub function: FnStmt,
+}
+
+/// A string with interpolated expressions, `"a ${x} b"`, which concatenates
+/// its parts after converting them to strings.
+#[derive(Debug, Clone)]
+pub struct Interpolation {
+    pub quote: Token,
+    pub parts: Vec<Expr>,
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr = match self {
             Expr::Literal(l) => l.value.to_string(),
             Expr::Binary(b) => parenthize_expr!(&b.operator.lexme, b.left, b.right),
             Expr::Unary(u) => parenthize_expr!(&u.operator.lexme, u.right),
@@ -114,11 +171,45 @@ impl Expr {
             Expr::Variable(v) => parenthize_expr!(&v.name.lexme,),
             Expr::Assign(a) => parenthize_expr!(&a.name.lexme, a.value),
             Expr::Logical(l) => parenthize_expr!(&l.operator.lexme, l.left, l.right),
-            Expr::Call(c) => parenthize_expr!("fun", c.callee),
+            Expr::Call(c) => {
+                let args: Vec<String> = c.args.iter().map(ToString::to_string).collect();
+                parenthize_expr!("call", c.callee, args.join(" "))
+            }
             Expr::Get(g) => parenthize_expr!(&g.name.lexme, g.object),
             Expr::Set(s) => parenthize_expr!(&s.name.lexme, s.object, s.value),
             Expr::This(t) => parenthize_expr!(&t.keyword.lexme,),
-        }
+            Expr::Super(s) => parenthize_expr!(&s.keyword.lexme, s.method.lexme),
+            Expr::List(l) => {
+                let elements: Vec<String> = l.elements.iter().map(ToString::to_string).collect();
+                parenthize_expr!("list", elements.join(" "))
+            }
+            Expr::Map(m) => {
+                let entries: Vec<String> = m
+                    .entries
+                    .iter()
+                    .map(|(k, v)| parenthize_expr!(":", k, v))
+                    .collect();
+                parenthize_expr!("map", entries.join(" "))
+            }
+            Expr::Index(i) => parenthize_expr!("index", i.object, i.key),
+            Expr::SetIndex(s) => parenthize_expr!("set-index", s.object, s.key, s.value),
+            Expr::Lambda(l) => {
+                let params: Vec<&str> =
+                    l.function.params.iter().map(|p| p.lexme.as_str()).collect();
+                parenthize_expr!("lambda", params.join(" "))
+            }
+            Expr::Interpolation(i) => {
+                let parts: Vec<String> = i.parts.iter().map(ToString::to_string).collect();
+                parenthize_expr!("interpolate", parts.join(" "))
+            }
+        };
+        write!(f, "{repr}")
+    }
+}
+
+impl Expr {
+    pub fn is_assignment(&self) -> bool {
+        matches!(self, Expr::Assign(_) | Expr::Set(_) | Expr::SetIndex(_))
     }
 
     pub fn literal(literal_type: LiteralValue) -> Self {
@@ -168,9 +259,9 @@ impl Expr {
         })
     }
 
-    pub fn call(callee: String, paren: Token, args: Vec<Expr>) -> Self {
+    pub fn call(callee: Expr, paren: Token, args: Vec<Expr>) -> Self {
         Expr::Call(Call {
-            callee,
+            callee: Box::new(callee),
             paren,
             args,
         })
@@ -192,8 +283,55 @@ impl Expr {
         })
     }
 
-    pub fn this(keyword: Token) -> Self {
-        Expr::This(This { keyword })
+    pub fn list(bracket: Token, elements: Vec<Expr>) -> Self {
+        Expr::List(List { bracket, elements })
+    }
+
+    pub fn map(brace: Token, entries: Vec<(Expr, Expr)>) -> Self {
+        Expr::Map(Map { brace, entries })
+    }
+
+    pub fn index(object: Expr, bracket: Token, key: Expr) -> Self {
+        Expr::Index(Index {
+            object: Box::new(object),
+            bracket,
+            key: Box::new(key),
+        })
+    }
+
+    pub fn set_index(object: Expr, bracket: Token, key: Expr, value: Expr) -> Self {
+        Expr::SetIndex(SetIndex {
+            object: Box::new(object),
+            bracket,
+            key: Box::new(key),
+            value: Box::new(value),
+        })
+    }
+
+    pub fn lambda(function: FnStmt) -> Self {
+        Expr::Lambda(Lambda { function })
+    }
+
+    pub fn interpolation(quote: Token, parts: Vec<Expr>) -> Self {
+        Expr::Interpolation(Interpolation { quote, parts })
+    }
+
+    pub fn this(keyword: Token, id: usize) -> Self {
+        Expr::This(This { keyword, id })
+    }
+
+    pub fn super_expr(
+        keyword: Token,
+        method: Token,
+        method_args: Option<Vec<Expr>>,
+        id: usize,
+    ) -> Self {
+        Expr::Super(Super {
+            keyword,
+            method,
+            method_args,
+            id,
+        })
     }
 }
 
@@ -205,6 +343,8 @@ pub fn liter_expr() {
 
 #[test]
 pub fn group_expr() {
+    use crate::token::TokenType;
+
     let multiplication = Token {
         column: 0,
         line: 0,
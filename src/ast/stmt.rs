@@ -1,8 +1,9 @@
 use crate::token::{LiteralValue, Token};
 
-use super::expr::Expr;
+use super::expr::{Expr, ExprId, Variable};
 
 #[derive(Clone, Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum Stmt {
     Expresssion(ExprStmt),
     Print(ExprStmt),
@@ -11,17 +12,23 @@ pub enum Stmt {
     IfStmt(IfStmt),
     WhileStmt(WhileStmt),
     ForStmt(ForStmt),
+    ForInStmt(ForInStmt),
     FnStmt(FnStmt),
     ReturnStmt(ReturnStmt),
     Class(Class),
+    Trait(TraitStmt),
+    Import(ImportStmt),
     BreakStmt(BreakStmt),
     ContStmt(ContStmt),
+    ThrowStmt(ThrowStmt),
+    TryStmt(TryStmt),
 }
 
 #[derive(Clone, Debug)]
 pub struct VarStmt {
     pub name: Token,
     pub initializer: Expr,
+    pub id: ExprId,
 }
 
 #[derive(Clone, Debug)]
@@ -61,11 +68,22 @@ pub struct ForStmt {
     pub body: Box<Stmt>,
 }
 
+/// `for (var name in iterable) body`, `keyword` is the `in` token.
+#[derive(Clone, Debug)]
+pub struct ForInStmt {
+    pub name: Token,
+    pub keyword: Token,
+    pub iterable: Expr,
+    pub body: Box<Stmt>,
+    pub id: ExprId,
+}
+
 #[derive(Clone, Debug)]
 pub struct FnStmt {
     pub name: Token,
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
+    pub id: ExprId,
 }
 
 #[derive(Clone, Debug)]
@@ -81,20 +99,85 @@ pub struct BreakStmt {}
 pub struct ContStmt {}
 
 #[derive(Clone, Debug)]
-pub struct Class {
+pub struct ThrowStmt {
+    pub keyword: Token,
+    pub value: Expr,
+}
+
+/// `catch (name) { body }`, the caught value is bound to `name`.
+#[derive(Clone, Debug)]
+pub struct CatchClause {
     pub name: Token,
+    pub body: Vec<Stmt>,
+    pub id: ExprId,
+}
+
+/// A `try` block followed by a `catch` clause, a `finally` block or both.
+#[derive(Clone, Debug)]
+pub struct TryStmt {
+    pub keyword: Token,
+    pub body: BlockStmt,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<BlockStmt>,
+}
+
+/// The members declared between a class's braces.
+#[derive(Clone, Debug, Default)]
+pub struct ClassBody {
     pub methods: Vec<FnStmt>,
+    /// `get name { body }`, run when the property is read.
+    pub getters: Vec<FnStmt>,
+    /// `set name(value) { body }`, run when the property is assigned.
+    pub setters: Vec<FnStmt>,
+    /// `var` declarations, their initializers give each instance its defaults.
+    pub fields: Vec<VarStmt>,
+    /// `static` members belong to the class itself, `this` is the class in
+    /// static methods.
+    pub static_methods: Vec<FnStmt>,
+    pub static_fields: Vec<VarStmt>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Class {
+    pub name: Token,
+    pub superclass: Option<Variable>,
+    /// The traits after `with`.
+    pub traits: Vec<Variable>,
+    pub body: ClassBody,
     pub params: Vec<Token>,
+    pub id: ExprId,
+}
+
+/// `trait Name { fun required(); fun provided() { body } }`. The parameter
+/// lists of required methods only document them.
+#[derive(Clone, Debug)]
+pub struct TraitStmt {
+    pub name: Token,
+    pub required: Vec<Token>,
+    /// Default methods, used by classes that don't define their own.
+    pub methods: Vec<FnStmt>,
+    pub id: ExprId,
+}
+
+/// `import "path" as name;` binds the module itself, `from "path" import a, b;`
+/// binds some of its exports.
+#[derive(Clone, Debug)]
+pub struct ImportStmt {
+    pub keyword: Token,
+    pub path: Token,
+    pub alias: Option<Token>,
+    pub names: Vec<Token>,
 }
 
 impl Stmt {
-    pub fn var(name: Token, initializer: Option<Expr>) -> Self {
+    pub fn var(name: Token, initializer: Option<Expr>, id: ExprId) -> Self {
         Stmt::Var(VarStmt {
             name,
             initializer: match initializer {
                 Some(v) => v,
                 None => Expr::literal(LiteralValue::Nil),
             },
+            id,
         })
     }
 
@@ -114,7 +197,7 @@ impl Stmt {
         Stmt::IfStmt(IfStmt {
             condition,
             then_branch: Box::new(then_branch),
-            else_branch: else_branch.map(|v| Box::new(v)),
+            else_branch: else_branch.map(Box::new),
         })
     }
 
@@ -139,22 +222,86 @@ impl Stmt {
         })
     }
 
+    pub fn for_in_stmt(
+        name: Token,
+        keyword: Token,
+        iterable: Expr,
+        body: Stmt,
+        id: ExprId,
+    ) -> Self {
+        Stmt::ForInStmt(ForInStmt {
+            name,
+            keyword,
+            iterable,
+            body: Box::new(body),
+            id,
+        })
+    }
+
     // pub fn fn_stmt(name: Token, params: Vec<Token>, body: Vec<Stmt>) -> Self {
-        // Stmt::FnStmt(FnStmt { name, params, body })
+    // Stmt::FnStmt(FnStmt { name, params, body })
     // }
 
     pub fn return_stmt(keyword: Token, value: Option<Expr>) -> Self {
-        Stmt::ReturnStmt(ReturnStmt {
+        Stmt::ReturnStmt(ReturnStmt { keyword, value })
+    }
+
+    pub fn throw_stmt(keyword: Token, value: Expr) -> Self {
+        Stmt::ThrowStmt(ThrowStmt { keyword, value })
+    }
+
+    pub fn try_stmt(
+        keyword: Token,
+        body: Vec<Stmt>,
+        catch: Option<CatchClause>,
+        finally: Option<Vec<Stmt>>,
+    ) -> Self {
+        Stmt::TryStmt(TryStmt {
             keyword,
-            value,
+            body: BlockStmt { statements: body },
+            catch,
+            finally: finally.map(|statements| BlockStmt { statements }),
         })
     }
 
-    pub fn class_stmt(name: Token, methods: Vec<FnStmt>, params: Vec<Token>) -> Self {
+    pub fn class_stmt(
+        name: Token,
+        superclass: Option<Variable>,
+        traits: Vec<Variable>,
+        body: ClassBody,
+        params: Vec<Token>,
+        id: ExprId,
+    ) -> Self {
         Stmt::Class(Class {
             name,
-            methods,
+            superclass,
+            traits,
+            body,
             params,
+            id,
+        })
+    }
+
+    pub fn trait_stmt(name: Token, required: Vec<Token>, methods: Vec<FnStmt>, id: ExprId) -> Self {
+        Stmt::Trait(TraitStmt {
+            name,
+            required,
+            methods,
+            id,
+        })
+    }
+
+    pub fn import_stmt(
+        keyword: Token,
+        path: Token,
+        alias: Option<Token>,
+        names: Vec<Token>,
+    ) -> Self {
+        Stmt::Import(ImportStmt {
+            keyword,
+            path,
+            alias,
+            names,
         })
     }
 }
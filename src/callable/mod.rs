@@ -1,26 +1,105 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
 
-use crate::ast::stmt::FnStmt;
+use crate::{ast::stmt::FnStmt, class::RloxInstance, environment::Environment, token::RloxValue};
 
 #[derive(Debug)]
 pub struct Callable {
-    pub function: Rc<FnStmt>
+    pub function: Rc<FnStmt>,
+    pub closure: Environment,
+    /// Globals of the file that declared the function, which its body sees.
+    pub globals: Environment,
 }
 
 impl Callable {
-    pub fn new(fun: FnStmt) -> Self {
-        Callable { function: Rc::new(fun) }
+    pub fn new(fun: FnStmt, closure: Environment, globals: Environment) -> Self {
+        Callable {
+            function: Rc::new(fun),
+            closure,
+            globals,
+        }
+    }
+
+    /// Returns a copy of this method whose closure has `this` bound to `instance`,
+    /// in the only slot of the scope the resolver opens for it.
+    pub fn bind(&self, instance: &RloxInstance) -> Self {
+        self.bind_this(RloxValue::Instance(instance.clone()))
+    }
+
+    /// Like [`Callable::bind`], static methods have their class as `this`.
+    pub fn bind_this(&self, this: RloxValue) -> Self {
+        let mut env = Environment::new();
+        env.add_enclosing(&self.closure);
+        env.define_at(0, this);
+        Callable {
+            function: self.function.clone(),
+            closure: env,
+            globals: self.globals.clone(),
+        }
     }
 }
 
 impl Clone for Callable {
     fn clone(&self) -> Self {
-        Callable { function: self.function.clone() }
+        Callable {
+            function: self.function.clone(),
+            closure: self.closure.clone(),
+            globals: self.globals.clone(),
+        }
     }
 }
 
-impl std::fmt::Display for Callable {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for Callable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "fun {}", self.function.name.lexme)
     }
 }
+
+pub type NativeFn = Rc<dyn Fn(&[RloxValue]) -> Result<RloxValue, String>>;
+
+/// A function implemented in Rust. An `Err` is reported as a runtime error
+/// at the call site.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+    /// Whether the interpreter formats the arguments into strings first, the
+    /// way `print` does, running `__str__` methods.
+    pub formats_args: bool,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[RloxValue]) -> Result<RloxValue, String> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Rc::new(function),
+            formats_args: false,
+        }
+    }
+
+    /// The same function, called with its arguments formatted into strings.
+    #[must_use]
+    pub fn formatting_args(self) -> Self {
+        NativeFunction {
+            formats_args: true,
+            ..self
+        }
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeFunction({}/{})", self.name, self.arity)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
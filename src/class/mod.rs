@@ -1,25 +1,129 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{callable::Callable, token::RloxValue};
+use crate::{ast::stmt::VarStmt, callable::Callable, environment::Environment, token::RloxValue};
+
+/// The functions declared in a class body, by name.
+#[derive(Debug, Clone, Default)]
+pub struct ClassFunctions {
+    pub methods: HashMap<String, Callable>,
+    pub getters: HashMap<String, Callable>,
+    pub setters: HashMap<String, Callable>,
+    pub statics: HashMap<String, Callable>,
+}
+
+impl ClassFunctions {
+    pub fn all(&self) -> impl Iterator<Item = &Callable> {
+        self.methods
+            .values()
+            .chain(self.getters.values())
+            .chain(self.setters.values())
+            .chain(self.statics.values())
+    }
+}
+
+/// A set of methods classes can be composed with. `required` methods have to
+/// be implemented by the class, the others are defaults it can override.
+#[derive(Debug)]
+pub struct RloxTrait {
+    pub name: String,
+    pub required: Vec<String>,
+    pub methods: HashMap<String, Callable>,
+}
 
 #[derive(Debug, Clone)]
 pub struct RloxClass {
     pub name: String,
-    pub methods: HashMap<String, Callable>,
+    pub superclass: Option<Rc<RloxClass>>,
+    pub functions: ClassFunctions,
+    pub traits: Vec<Rc<RloxTrait>>,
+    /// Field declarations, evaluated in `closure` for every new instance.
+    pub fields: Vec<VarStmt>,
+    pub closure: Environment,
     pub params: Vec<String>,
+    pub static_fields: RefCell<HashMap<String, RloxValue>>,
 }
 
 impl RloxClass {
-    pub fn new(name: String, methods: HashMap<String, Callable>, params: Vec<String>) -> Self {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<RloxClass>>,
+        functions: ClassFunctions,
+        traits: Vec<Rc<RloxTrait>>,
+        fields: Vec<VarStmt>,
+        closure: Environment,
+        params: Vec<String>,
+    ) -> Self {
         RloxClass {
             name,
-            methods,
+            superclass,
+            functions,
+            traits,
+            fields,
+            closure,
             params,
+            static_fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Looks `name` up in the functions `kind` picks, then in the superclasses.
+    fn find_function(
+        &self,
+        name: &str,
+        kind: fn(&ClassFunctions) -> &HashMap<String, Callable>,
+    ) -> Option<&Callable> {
+        match kind(&self.functions).get(name) {
+            Some(m) => Some(m),
+            None => self.superclass.as_ref()?.find_function(name, kind),
         }
     }
 
+    /// Looks `name` up in the class, then in the defaults of its traits in
+    /// the order they were listed, then in the superclasses.
     pub fn find_method(&self, name: &str) -> Option<&Callable> {
-        self.methods.get(name)
+        self.functions
+            .methods
+            .get(name)
+            .or_else(|| self.traits.iter().find_map(|t| t.methods.get(name)))
+            .or_else(|| self.superclass.as_ref()?.find_method(name))
+    }
+
+    /// Whether the class or one of its superclasses was composed with `t`.
+    pub fn implements(&self, t: &Rc<RloxTrait>) -> bool {
+        self.traits.iter().any(|own| Rc::ptr_eq(own, t))
+            || self.superclass.as_ref().is_some_and(|s| s.implements(t))
+    }
+
+    pub fn find_getter(&self, name: &str) -> Option<&Callable> {
+        self.find_function(name, |f| &f.getters)
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<&Callable> {
+        self.find_function(name, |f| &f.setters)
+    }
+
+    /// Looks up a static member, subclasses share the static members of
+    /// their superclasses.
+    pub fn get_static(&self, name: &str) -> Option<FieldType> {
+        if let Some(f) = self.static_fields.borrow().get(name) {
+            return Some(FieldType::Field(f.clone()));
+        }
+        if let Some(m) = self.functions.statics.get(name) {
+            return Some(FieldType::Method(m.clone()));
+        }
+        self.superclass.as_ref()?.get_static(name)
+    }
+
+    pub fn define_static(&self, name: String, value: RloxValue) {
+        self.static_fields.borrow_mut().insert(name, value);
+    }
+
+    /// Replaces the value of a static field in the class that declares it,
+    /// returning the old one.
+    pub fn set_static(&self, name: &str, value: RloxValue) -> Option<RloxValue> {
+        if let Some(field) = self.static_fields.borrow_mut().get_mut(name) {
+            return Some(std::mem::replace(field, value));
+        }
+        self.superclass.as_ref()?.set_static(name, value)
     }
 }
 
@@ -31,32 +135,40 @@ impl std::fmt::Display for RloxClass {
 
 #[derive(Debug)]
 struct ClassCtx {
-    pub class: RloxClass,
+    pub class: Rc<RloxClass>,
     pub fields: HashMap<String, RloxValue>,
 }
 
 #[derive(Debug)]
 pub struct RloxInstance {
-    ctx: Rc<RefCell<ClassCtx>>
+    ctx: Rc<RefCell<ClassCtx>>,
 }
 
 pub enum FieldType {
     Method(Callable),
     Field(RloxValue),
+    /// Has to be called to get the property's value.
+    Getter(Callable),
+    /// Has to be called with the assigned value.
+    Setter(Callable),
 }
 
 impl RloxInstance {
-    pub fn new(class: RloxClass, args: Vec<RloxValue>) -> Self {
-        let mut fields = HashMap::new();
-        for (i, arg) in args.into_iter().enumerate() {
-            fields.insert(class.params[i].to_string(), arg);
-        }
+    pub fn new(class: Rc<RloxClass>, fields: HashMap<String, RloxValue>) -> Self {
         let ctx = ClassCtx { class, fields };
-        RloxInstance { ctx: Rc::new(RefCell::new(ctx)) }
+        RloxInstance {
+            ctx: Rc::new(RefCell::new(ctx)),
+        }
     }
 
+    /// Looks up a property: getters come first, then fields and methods. A
+    /// setter is only returned when nothing else has the name, so that the
+    /// caller can tell a write-only property from a missing one.
     pub fn get(&self, name: &str) -> Option<FieldType> {
         let ctx = self.ctx.borrow();
+        if let Some(g) = ctx.class.find_getter(name) {
+            return Some(FieldType::Getter(g.clone()));
+        }
         if let Some(f) = ctx.fields.get(name) {
             return Some(FieldType::Field(f.clone()));
         }
@@ -65,12 +177,57 @@ impl RloxInstance {
             return Some(FieldType::Method(m.clone()));
         }
 
-        None
+        ctx.class
+            .find_setter(name)
+            .map(|s| FieldType::Setter(s.clone()))
+    }
+
+    pub fn class_name(&self) -> String {
+        self.ctx.borrow().class.name.clone()
+    }
+
+    pub fn class(&self) -> Rc<RloxClass> {
+        self.ctx.borrow().class.clone()
     }
 
-    pub fn set(&mut self, name: String, value: RloxValue) -> Option<RloxValue> {
+    /// Replaces the value of a declared field, returning the old one, or
+    /// returns the setter to call instead. Fields can't be added after the
+    /// instance was created.
+    pub fn set(&mut self, name: &str, value: RloxValue) -> Option<FieldType> {
         let mut ctx = self.ctx.borrow_mut();
-        ctx.fields.insert(name, value)
+        if let Some(s) = ctx.class.find_setter(name) {
+            return Some(FieldType::Setter(s.clone()));
+        }
+        let field = ctx.fields.get_mut(name)?;
+        Some(FieldType::Field(std::mem::replace(field, value)))
+    }
+
+    pub fn has_getter(&self, name: &str) -> bool {
+        self.ctx.borrow().class.find_getter(name).is_some()
+    }
+
+    /// The method `name` bound to this instance, without looking at fields.
+    pub fn bound_method(&self, name: &str) -> Option<Callable> {
+        let method = self.ctx.borrow().class.find_method(name)?.clone();
+        Some(method.bind(self))
+    }
+
+    /// Whether both values are the same instance.
+    pub fn ptr_eq(&self, other: &RloxInstance) -> bool {
+        Rc::ptr_eq(&self.ctx, &other.ctx)
+    }
+
+    /// Calls `f` with the class and fields, unless the instance is shared or
+    /// being changed, for the collector to follow what a scope owns.
+    pub(crate) fn with_owned_parts(
+        &self,
+        f: impl FnOnce(&Rc<RloxClass>, &HashMap<String, RloxValue>),
+    ) {
+        if Rc::strong_count(&self.ctx) == 1 {
+            if let Ok(ctx) = self.ctx.try_borrow() {
+                f(&ctx.class, &ctx.fields);
+            }
+        }
     }
 }
 
@@ -82,8 +239,8 @@ impl std::fmt::Display for RloxInstance {
 
 impl Clone for RloxInstance {
     fn clone(&self) -> Self {
-        RloxInstance { ctx: self.ctx.clone() }
+        RloxInstance {
+            ctx: self.ctx.clone(),
+        }
     }
 }
-
-
@@ -0,0 +1,280 @@
+use std::{
+    env, fs,
+    io::{self, Write},
+    mem,
+    path::Path,
+    process::ExitCode,
+    thread,
+};
+
+use crate::{
+    errors::{scanner_errors::ScannerError, LoxError, ReportError, StackFrame},
+    lexer::scanner::Scanner,
+    session::{self, Session},
+    token::{RloxValue, TokenType},
+    vm::{compiler::Compiler, Vm},
+};
+
+const USAGE: &str = "usage: ./rlox [--backend=tree|vm] [file]";
+
+/// Enough for [`crate::vm::MAX_FRAMES`] nested calls in the tree walker,
+/// whose every Lox call is a few dozen Rust ones, even in debug builds.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Runs the file named on the command line, or a prompt without one, and
+/// returns the exit code of the `rlox` binary.
+///
+/// # Panics
+///
+/// Panics if the interpreter thread can't be started.
+#[must_use]
+pub fn run_cli() -> ExitCode {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_args)
+        .expect("Unable to start the interpreter thread!")
+        .join()
+        .unwrap_or(ExitCode::FAILURE)
+}
+
+fn run_args() -> ExitCode {
+    let mut backend = Backend::default();
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.strip_prefix("--backend=") {
+            Some("tree") => backend = Backend::TreeWalk,
+            Some("vm") => backend = Backend::Vm,
+            Some(_) => {
+                println!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+            None => files.push(arg),
+        }
+    }
+
+    let mut rlox = Rlox::new(backend);
+    match files.as_slice() {
+        [] => {
+            rlox.run_prompt();
+            ExitCode::SUCCESS
+        }
+        [file] => rlox.run_file(file),
+        _ => {
+            println!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// How a resolved program gets executed.
+#[derive(Clone, Copy, Default)]
+pub enum Backend {
+    #[default]
+    TreeWalk,
+    Vm,
+}
+
+/// The command line interpreter: runs a file or a prompt and reports errors
+/// with the offending source line.
+pub struct Rlox {
+    had_error: bool,
+    backend: Backend,
+    session: Session,
+    vm: Vm,
+    last_id: usize,
+}
+
+impl Default for Rlox {
+    fn default() -> Self {
+        Self::new(Backend::default())
+    }
+}
+
+impl Rlox {
+    #[must_use]
+    pub fn new(backend: Backend) -> Self {
+        Rlox {
+            had_error: false,
+            backend,
+            session: Session::new(),
+            vm: Vm::new(),
+            last_id: 0,
+        }
+    }
+
+    /// Runs `source` against the state left by earlier runs. Errors are
+    /// reported and set `had_error`, the caller decides whether to go on.
+    fn run(&mut self, source: &str, interactive: bool) {
+        let result = match self.backend {
+            Backend::TreeWalk => self.session.run(source, interactive),
+            Backend::Vm => self.run_vm(source, interactive),
+        };
+        match result.and_then(|value| match value {
+            RloxValue::Nil => Ok(None),
+            value => self.session.stringify(&value).map(Some),
+        }) {
+            Ok(None) => {}
+            Ok(Some(text)) => println!("{text}"),
+            Err(e) => {
+                let line_text = source.split('\n').collect::<Vec<&str>>();
+                for error in e.errors() {
+                    let text = line_text.get(error.get_line()).copied().unwrap_or_default();
+                    self.report_error(error, text);
+                }
+                if let LoxError::Runtime(_, traceback) = &e {
+                    Rlox::report_traceback(traceback);
+                }
+            }
+        }
+    }
+
+    /// Compiles and runs `source` on the VM, which echoes interactive
+    /// expressions itself.
+    fn run_vm(&mut self, source: &str, interactive: bool) -> Result<RloxValue, LoxError> {
+        let (statements, _) = session::analyze(source, interactive, &mut self.last_id)?;
+        let script = if interactive {
+            Compiler::compile_interactive(&statements)
+        } else {
+            Compiler::compile(&statements)
+        };
+        let script = script.map_err(LoxError::Compile)?;
+        self.vm
+            .interpret(script)
+            .map_err(|e| LoxError::Runtime(e, self.vm.take_traceback()))?;
+        Ok(RloxValue::Nil)
+    }
+
+    /// Whether `source` ends inside a block, parentheses, a string or a
+    /// comment, or right after an operator, so that the prompt should keep
+    /// reading lines.
+    fn needs_more_input(source: &str) -> bool {
+        let mut scanner = Scanner::new(source);
+        match scanner.scan_tokens() {
+            Ok(()) => {
+                let open: isize = scanner
+                    .tokens
+                    .iter()
+                    .map(|t| match t.r#type {
+                        TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => 1,
+                        TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                            -1
+                        }
+                        _ => 0,
+                    })
+                    .sum();
+                let last = scanner.tokens.iter().rfind(|t| t.r#type != TokenType::Eof);
+                open > 0 || last.is_some_and(|t| Rlox::expects_operand(&t.r#type))
+            }
+            Err(ScannerError::StringError(_) | ScannerError::CommentError(_)) => true,
+            Err(ScannerError::TokenError(_) | ScannerError::LiteralError(_)) => false,
+        }
+    }
+
+    /// Whether an expression can't end with a token of type `r#type`.
+    fn expects_operand(r#type: &TokenType) -> bool {
+        matches!(
+            r#type,
+            TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Star
+                | TokenType::Slash
+                | TokenType::Bang
+                | TokenType::BangEqual
+                | TokenType::Equal
+                | TokenType::EqualEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::And
+                | TokenType::Or
+                | TokenType::Dot
+                | TokenType::Comma
+                | TokenType::Colon
+                | TokenType::Arrow
+        )
+    }
+
+    /// Reads and runs lines from stdin until it is closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if stdout can't be written to.
+    pub fn run_prompt(&mut self) {
+        let mut source = String::new();
+        loop {
+            let prompt = if source.is_empty() { "> " } else { "... " };
+            io::stdout()
+                .write_all(prompt.as_bytes())
+                .expect("Unable to write to stdout!");
+            io::stdout().flush().expect("Could not flush buffer!");
+            let read = io::stdin()
+                .read_line(&mut source)
+                .expect("Unable to parse from stdin!");
+            if read == 0 {
+                // end of input
+                println!();
+                return;
+            }
+            if Rlox::needs_more_input(&source) {
+                continue;
+            }
+            self.run(&mem::take(&mut source), true);
+            self.had_error = false;
+        }
+    }
+
+    /// Runs the script at `path`, exiting with 0x41 if it fails and
+    /// `EX_NOINPUT` if it can't be read.
+    pub fn run_file(&mut self, path: &str) -> ExitCode {
+        self.session.set_path(Path::new(path));
+        match fs::read_to_string(path) {
+            Ok(s) => self.run(&s, false),
+            Err(e) => {
+                eprintln!("Error reading file: {e}");
+                return ExitCode::from(0x42);
+            }
+        }
+
+        if self.had_error {
+            ExitCode::from(0x41)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+
+    fn report_traceback(traceback: &[StackFrame]) {
+        if traceback.is_empty() {
+            return;
+        }
+        eprintln!("traceback, innermost call first:");
+        // deep recursion repeats one frame, shown once with a count
+        for frames in traceback.chunk_by(|a, b| a == b) {
+            eprintln!("    {}", frames[0]);
+            if frames.len() > 1 {
+                eprintln!("    ... {} more", frames.len() - 1);
+            }
+        }
+    }
+
+    fn report_error(&mut self, error: &dyn ReportError, text: &str) {
+        let column = error.get_column();
+        let line = error.get_line();
+        let message = error.get_msg();
+        let l_pad = "    ";
+        let mut offset = String::new();
+        for _ in 2..column {
+            offset.push(' ');
+        }
+        let text_lines: Vec<&str> = text.lines().collect();
+        eprintln!("\x1b[37;41m Error \x1b[0m: {message}");
+        println!("{l_pad}|");
+        for i in 1..=text_lines.len() {
+            let line_num = line + i + 1 - text_lines.len();
+            let l_pad = if line_num > 9 { "  " } else { "   " };
+            println!("{}{}| {}", line_num, l_pad, text_lines[i - 1]);
+        }
+        println!("{l_pad}| {offset}^^");
+        self.had_error = true;
+    }
+}
@@ -1,162 +1,481 @@
-use std::{collections::HashMap, ptr::NonNull};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+};
 
-use crate::{class::RloxClass, token::RloxValue};
+use crate::{
+    callable::Callable,
+    class::{RloxClass, RloxTrait},
+    token::RloxValue,
+};
 
-pub type NonNullCtx = NonNull<EnvCtx>;
+type EnvPtr = *const RefCell<EnvCtx>;
 
+const MIN_COLLECT_THRESHOLD: usize = 1024;
+
+/// A scope in the chain of lexical environments.
+///
+/// Locals live in `slots`, at the index the resolver assigned them. Globals
+/// can't be resolved ahead of time and are looked up by name instead.
+///
+/// Scopes are shared through reference counting, so a closure, bound method or
+/// child scope keeps everything it encloses alive. Reference cycles (a function
+/// stored in the scope it closes over, or in a field of an instance that scope
+/// holds) are reclaimed by [`Environment::collect_garbage`].
+#[derive(Clone)]
 pub struct Environment {
-    ctx: NonNullCtx,
+    ctx: Rc<RefCell<EnvCtx>>,
 }
 
-pub struct EnvCtx {
+#[derive(Default)]
+struct EnvCtx {
+    slots: Vec<RloxValue>,
     rlox_vars: HashMap<String, RloxValue>,
-    rlox_classes: HashMap<String, RloxClass>,
-    pub enclosing: Option<NonNullCtx>,
+    enclosing: Option<Environment>,
+}
+
+/// Every scope allocated on this thread, used to find unreachable cycles.
+struct EnvHeap {
+    envs: Vec<Weak<RefCell<EnvCtx>>>,
+    threshold: usize,
+}
+
+thread_local! {
+    static HEAP: RefCell<EnvHeap> = const {
+        RefCell::new(EnvHeap {
+            envs: Vec::new(),
+            threshold: MIN_COLLECT_THRESHOLD,
+        })
+    };
+}
+
+impl EnvCtx {
+    /// Calls `f` for every scope this one holds a strong reference to that the
+    /// collector is able to account for.
+    fn for_each_edge(&self, mut f: impl FnMut(EnvPtr)) {
+        if let Some(e) = &self.enclosing {
+            f(Rc::as_ptr(&e.ctx));
+        }
+        for value in self.slots.iter().chain(self.rlox_vars.values()) {
+            EnvCtx::value_edges(value, &mut f);
+        }
+    }
+
+    /// Follows a value held by a scope to the scopes it holds. Instances,
+    /// lists and maps shared with anything else are treated as external
+    /// roots, like classes.
+    fn value_edges(value: &RloxValue, f: &mut impl FnMut(EnvPtr)) {
+        match value {
+            RloxValue::Callable(c) => EnvCtx::callable_edges(c, f),
+            RloxValue::Class(c) => EnvCtx::class_edges(c, f),
+            RloxValue::Trait(t) => EnvCtx::trait_edges(t, f),
+            RloxValue::Instance(i) => i.with_owned_parts(|class, fields| {
+                EnvCtx::class_edges(class, f);
+                for field in fields.values() {
+                    EnvCtx::value_edges(field, f);
+                }
+            }),
+            RloxValue::List(l) => l.for_each_owned(|v| EnvCtx::value_edges(v, f)),
+            RloxValue::Map(m) => m.for_each_owned(|v| EnvCtx::value_edges(v, f)),
+            _ => (),
+        }
+    }
+
+    fn callable_edges(c: &Callable, f: &mut impl FnMut(EnvPtr)) {
+        f(Rc::as_ptr(&c.closure.ctx));
+        f(Rc::as_ptr(&c.globals.ctx));
+    }
+
+    fn class_edges(class: &Rc<RloxClass>, f: &mut impl FnMut(EnvPtr)) {
+        // a class shared with anything else is treated as an external root
+        let mut class = Some(class);
+        while let Some(c) = class.filter(|c| Rc::strong_count(c) == 1) {
+            f(Rc::as_ptr(&c.closure.ctx));
+            for method in c.functions.all() {
+                EnvCtx::callable_edges(method, f);
+            }
+            for value in c.static_fields.borrow().values() {
+                EnvCtx::value_edges(value, f);
+            }
+            for t in &c.traits {
+                EnvCtx::trait_edges(t, f);
+            }
+            class = c.superclass.as_ref();
+        }
+    }
+
+    fn trait_edges(t: &Rc<RloxTrait>, f: &mut impl FnMut(EnvPtr)) {
+        if Rc::strong_count(t) == 1 {
+            for method in t.methods.values() {
+                EnvCtx::callable_edges(method, f);
+            }
+        }
+    }
 }
 
 impl Environment {
     pub fn new() -> Environment {
-        let ctx = unsafe {
-            NonNull::new_unchecked(Box::into_raw(Box::new(EnvCtx {
-                rlox_vars: HashMap::new(),
-                rlox_classes: HashMap::new(),
-                enclosing: None,
-            })))
-        };
+        Environment::maybe_collect_garbage();
+        let ctx = Rc::new(RefCell::new(EnvCtx::default()));
+        HEAP.with(|h| h.borrow_mut().envs.push(Rc::downgrade(&ctx)));
         Environment { ctx }
     }
 
-    pub fn add_enclosing(&mut self, enclosing: &Environment) {
-        let mut env = self.ctx;
-        unsafe {
-            let mut_env = env.as_mut();
-            mut_env.enclosing = Some(enclosing.ctx.clone());
-        }
-    }
-
-    pub fn get_at<'a>(
-        &mut self,
-        distance: usize,
-        name: String,
-    ) -> Result<Option<&'a RloxValue>, ()> {
-        unsafe {
-            let mut env = self.ctx.as_mut();
-            for _ in 0..distance {
-                match env.enclosing {
-                    Some(mut e) => env = e.as_mut(),
-                    None => return Err(()),
-                }
-            }
-            Ok(env.rlox_vars.get(&name))
-        }
-    }
-
-    pub fn assign_at(
-        &mut self,
-        distance: usize,
-        name: String,
-        value: RloxValue,
-    ) -> Result<(), ()> {
-        unsafe {
-            let mut env = self.ctx.as_mut();
-            for _ in 0..distance {
-                match env.enclosing {
-                    Some(mut e) => env = e.as_mut(),
-                    None => return Err(()),
+    /// Runs [`Environment::collect_garbage`] once enough scopes were allocated
+    /// since the last collection.
+    fn maybe_collect_garbage() {
+        let due = HEAP.with(|h| {
+            let h = h.borrow();
+            h.envs.len() >= h.threshold
+        });
+        if due {
+            Environment::collect_garbage();
+        }
+    }
+
+    /// Frees scopes that are only kept alive by reference cycles between scopes.
+    ///
+    /// A scope is live if it is referenced from anywhere other than another
+    /// scope (an interpreter frame, a shared instance, a temporary value) or if
+    /// it is reachable from such a scope. Everything else is cleared, which
+    /// breaks the cycles and lets reference counting free the memory.
+    pub fn collect_garbage() {
+        let envs: Vec<Rc<RefCell<EnvCtx>>> = HEAP.with(|h| {
+            let mut h = h.borrow_mut();
+            h.envs.retain(|e| e.strong_count() > 0);
+            h.envs.iter().filter_map(Weak::upgrade).collect()
+        });
+        let index: HashMap<EnvPtr, usize> = envs
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (Rc::as_ptr(e), i))
+            .collect();
+
+        let mut internal_refs = vec![0; envs.len()];
+        let mut edges = vec![Vec::new(); envs.len()];
+        for (i, env) in envs.iter().enumerate() {
+            let Ok(ctx) = env.try_borrow() else {
+                // a scope is being mutated, try again at the next safe point
+                return;
+            };
+            ctx.for_each_edge(|ptr| {
+                if let Some(&j) = index.get(&ptr) {
+                    internal_refs[j] += 1;
+                    edges[i].push(j);
                 }
+            });
+        }
+
+        // `envs` holds one strong reference to every scope itself
+        let mut reachable = vec![false; envs.len()];
+        let mut stack: Vec<usize> = (0..envs.len())
+            .filter(|&i| Rc::strong_count(&envs[i]) - 1 > internal_refs[i])
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !mem::replace(&mut reachable[i], true) {
+                stack.extend(&edges[i]);
             }
-            env.rlox_vars.insert(name, value);
-            Ok(())
         }
+
+        let garbage: Vec<EnvCtx> = envs
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, live)| !**live)
+            .map(|(env, _)| mem::take(&mut *env.borrow_mut()))
+            .collect();
+        let live = envs.len() - garbage.len();
+        drop(garbage);
+        drop(envs);
+
+        HEAP.with(|h| {
+            let mut h = h.borrow_mut();
+            h.envs.retain(|e| e.strong_count() > 0);
+            h.threshold = MIN_COLLECT_THRESHOLD.max(live * 2);
+        });
     }
 
-    pub fn define_var(&mut self, name: String, value: RloxValue) {
-        let mut env = self.ctx;
-        unsafe {
-            let mut_env = env.as_mut();
-            mut_env.rlox_vars.insert(name, value);
-        }
-    }
-
-    pub fn assign_var(&mut self, name: String, value: RloxValue) -> Result<(), ()> {
-        let mut env = self.ctx;
-        unsafe {
-            loop {
-                let mut_env = env.as_mut();
-                if mut_env.rlox_vars.contains_key(&name) {
-                    mut_env.rlox_vars.insert(name, value);
-                    return Ok(());
-                } else {
-                    match mut_env.enclosing {
-                        Some(e) => env = e,
-                        None => return Err(()),
-                    }
-                }
+    pub fn add_enclosing(&mut self, enclosing: &Environment) {
+        self.ctx.borrow_mut().enclosing = Some(enclosing.clone());
+    }
+
+    /// The outermost scope of the chain, where the globals of the file that
+    /// created this scope live.
+    pub fn root(&self) -> Environment {
+        let mut env = self.clone();
+        loop {
+            let enclosing = env.ctx.borrow().enclosing.clone();
+            match enclosing {
+                Some(e) => env = e,
+                None => return env,
             }
         }
     }
 
-    pub fn define_class(&mut self, name: String, value: RloxClass) {
-        let mut env = self.ctx;
-        unsafe {
-            let mut_env = env.as_mut();
-            mut_env.rlox_classes.insert(name, value);
+    fn ancestor(&self, distance: usize) -> Option<Environment> {
+        let mut env = self.clone();
+        for _ in 0..distance {
+            let enclosing = env.ctx.borrow().enclosing.clone();
+            env = enclosing?;
         }
+        Some(env)
     }
 
-    pub fn get_var<'a>(&mut self, name: &str) -> Option<&'a RloxValue> {
-        let mut env = self.ctx;
-        unsafe {
-            loop {
-                let mut_env = env.as_mut();
-                match mut_env.rlox_vars.get(name) {
-                    Some(v) => return Some(v),
-                    None => match mut_env.enclosing {
-                        Some(e) => env = e,
-                        None => return None,
-                    },
-                }
+    pub fn get_at(&self, distance: usize, slot: usize) -> Result<Option<RloxValue>, ()> {
+        if distance == 0 {
+            return Ok(self.ctx.borrow().slots.get(slot).cloned());
+        }
+        match self.ancestor(distance) {
+            Some(env) => Ok(env.ctx.borrow().slots.get(slot).cloned()),
+            None => Err(()),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, slot: usize, value: RloxValue) -> Result<(), ()> {
+        let env = self.ancestor(distance).ok_or(())?;
+        let mut ctx = env.ctx.borrow_mut();
+        match ctx.slots.get_mut(slot) {
+            Some(v) => {
+                *v = value;
+                Ok(())
             }
+            None => Err(()),
         }
     }
 
-    pub fn get_class(&mut self, name: &str) -> Option<&RloxClass> {
-        let mut env = self.ctx;
-        unsafe {
-            loop {
-                let mut_env = env.as_mut();
-                match mut_env.rlox_classes.get(name) {
-                    Some(v) => return Some(v),
-                    None => match mut_env.enclosing {
-                        Some(e) => env = e,
-                        None => return None,
-                    },
-                }
+    /// Stores a local declared in this scope, growing the slots if declarations
+    /// before it were skipped.
+    pub fn define_at(&mut self, slot: usize, value: RloxValue) {
+        let slots = &mut self.ctx.borrow_mut().slots;
+        if slot >= slots.len() {
+            slots.resize(slot + 1, RloxValue::Nil);
+        }
+        slots[slot] = value;
+    }
+
+    pub fn define_var(&mut self, name: String, value: RloxValue) {
+        self.ctx.borrow_mut().rlox_vars.insert(name, value);
+    }
+
+    pub fn assign_var(&mut self, name: &str, value: RloxValue) -> Result<(), ()> {
+        let mut env = self.clone();
+        loop {
+            if let Some(v) = env.ctx.borrow_mut().rlox_vars.get_mut(name) {
+                *v = value;
+                return Ok(());
+            }
+            let enclosing = env.ctx.borrow().enclosing.clone();
+            match enclosing {
+                Some(e) => env = e,
+                None => return Err(()),
             }
         }
     }
 
-    pub fn check(&mut self, name: &str) -> bool {
-        let mut env = self.ctx;
-        unsafe {
-            loop {
-                let mut_env = env.as_mut();
-                match mut_env.rlox_vars.contains_key(name) {
-                    true => return true,
-                    false => match mut_env.enclosing {
-                        Some(e) => env = e,
-                        None => return false,
-                    },
-                }
+    pub fn get_var(&self, name: &str) -> Option<RloxValue> {
+        let mut env = self.clone();
+        loop {
+            if let Some(v) = env.ctx.borrow().rlox_vars.get(name) {
+                return Some(v.clone());
             }
+            let enclosing = env.ctx.borrow().enclosing.clone();
+            env = enclosing?;
         }
     }
 }
 
-impl Drop for Environment {
-    fn drop(&mut self) {
-        unsafe {
-            let _ = Box::from_raw(self.ctx.as_ptr());
-        }
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Environment").finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+fn test_function(closure: &Environment) -> RloxValue {
+    use crate::{
+        ast::stmt::FnStmt,
+        token::{Token, TokenType},
+    };
+
+    let name = Token {
+        r#type: TokenType::Identifier,
+        lexme: "f".to_string(),
+        literal: None,
+        line: 0,
+        column: 0,
+    };
+    let fun = FnStmt {
+        name,
+        params: Vec::new(),
+        body: Vec::new(),
+        id: 0,
+    };
+    RloxValue::Callable(Callable::new(fun, closure.clone(), closure.root()))
+}
+
+#[test]
+#[allow(clippy::float_cmp)]
+pub fn nested_scopes() {
+    let mut global = Environment::new();
+    global.define_var("a".to_string(), RloxValue::Num(1.0));
+    let mut block = Environment::new();
+    block.add_enclosing(&global);
+    block.define_at(0, RloxValue::Num(2.0));
+    let mut inner = Environment::new();
+    inner.add_enclosing(&block);
+    inner.define_at(1, RloxValue::Num(3.0));
+
+    assert!(matches!(inner.get_at(0, 0), Ok(Some(RloxValue::Nil))));
+    assert!(matches!(inner.get_at(0, 1), Ok(Some(RloxValue::Num(n))) if n == 3.0));
+    assert!(matches!(inner.get_at(1, 0), Ok(Some(RloxValue::Num(n))) if n == 2.0));
+    assert!(matches!(inner.get_at(1, 1), Ok(None)));
+    assert!(inner.get_at(3, 0).is_err());
+
+    inner.assign_at(1, 0, RloxValue::Num(4.0)).unwrap();
+    assert!(inner.assign_at(1, 1, RloxValue::Nil).is_err());
+    drop(block);
+    assert!(matches!(inner.get_at(1, 0), Ok(Some(RloxValue::Num(n))) if n == 4.0));
+    assert!(matches!(inner.get_var("a"), Some(RloxValue::Num(n)) if n == 1.0));
+    assert!(matches!(global.get_var("a"), Some(RloxValue::Num(n)) if n == 1.0));
+}
+
+#[test]
+pub fn returned_function_outlives_scope() {
+    let global = Environment::new();
+    let returned = {
+        let mut call = Environment::new();
+        call.add_enclosing(&global);
+        call.define_at(0, RloxValue::Num(0.0));
+        test_function(&call)
+    };
+    let RloxValue::Callable(c) = returned else {
+        panic!("expected a function");
+    };
+    assert!(matches!(c.closure.get_at(0, 0), Ok(Some(RloxValue::Num(n))) if n == 0.0));
+}
+
+#[test]
+pub fn collects_unreachable_cycles() {
+    let global = Environment::new();
+    let mut call = Environment::new();
+    call.add_enclosing(&global);
+    let function = test_function(&call);
+    call.define_at(0, function);
+    let call_ctx = Rc::downgrade(&call.ctx);
+    drop(call);
+
+    assert!(call_ctx.upgrade().is_some());
+    Environment::collect_garbage();
+    assert!(call_ctx.upgrade().is_none());
+}
+
+#[test]
+pub fn keeps_reachable_cycles() {
+    let mut global = Environment::new();
+    let mut call = Environment::new();
+    call.add_enclosing(&global);
+    let function = test_function(&call);
+    call.define_at(0, function.clone());
+    global.define_var("f".to_string(), function);
+    let call_ctx = Rc::downgrade(&call.ctx);
+    drop(call);
+
+    Environment::collect_garbage();
+    assert!(call_ctx.upgrade().is_some());
+    let Some(RloxValue::Callable(c)) = global.get_var("f") else {
+        panic!("expected a function");
+    };
+    assert!(matches!(
+        c.closure.get_at(0, 0),
+        Ok(Some(RloxValue::Callable(_)))
+    ));
+
+    drop(c);
+    drop(global);
+    Environment::collect_garbage();
+    assert!(call_ctx.upgrade().is_none());
+}
+
+#[test]
+pub fn collects_unreachable_class_cycles() {
+    use crate::class::ClassFunctions;
+
+    let global = Environment::new();
+    let mut block = Environment::new();
+    block.add_enclosing(&global);
+    let RloxValue::Callable(method) = test_function(&block) else {
+        panic!("expected a function");
+    };
+    let functions = ClassFunctions {
+        statics: HashMap::from([("f".to_string(), method)]),
+        ..ClassFunctions::default()
+    };
+    let class = RloxClass::new(
+        "C".to_string(),
+        None,
+        functions,
+        Vec::new(),
+        Vec::new(),
+        block.clone(),
+        Vec::new(),
+    );
+    block.define_at(0, RloxValue::Class(Rc::new(class)));
+    let block_ctx = Rc::downgrade(&block.ctx);
+    drop(block);
+
+    assert!(block_ctx.upgrade().is_some());
+    Environment::collect_garbage();
+    assert!(block_ctx.upgrade().is_none());
+}
+
+#[test]
+pub fn collects_unreachable_instance_cycles() {
+    use crate::class::{ClassFunctions, RloxClass, RloxInstance};
+
+    // `init` storing a closure over `this` in a field of the instance
+    let global = Environment::new();
+    let class = RloxClass::new(
+        "C".to_string(),
+        None,
+        ClassFunctions::default(),
+        Vec::new(),
+        Vec::new(),
+        global.clone(),
+        Vec::new(),
+    );
+    let mut bound = Environment::new();
+    bound.add_enclosing(&global);
+    let mut call = Environment::new();
+    call.add_enclosing(&bound);
+    let fields = HashMap::from([("f".to_string(), test_function(&call))]);
+    let instance = RloxInstance::new(Rc::new(class), fields);
+    bound.define_at(0, RloxValue::Instance(instance));
+    let bound_ctx = Rc::downgrade(&bound.ctx);
+    drop(bound);
+    drop(call);
+
+    assert!(bound_ctx.upgrade().is_some());
+    Environment::collect_garbage();
+    assert!(bound_ctx.upgrade().is_none());
+}
+
+#[test]
+pub fn collects_unreachable_list_and_map_cycles() {
+    use crate::{list::RloxList, map::RloxMap};
+
+    let global = Environment::new();
+    let mut block = Environment::new();
+    block.add_enclosing(&global);
+    let list = RloxList::new(vec![test_function(&block)]);
+    let entries = vec![(RloxValue::Str("f".to_string()), test_function(&block))];
+    let map = RloxMap::new(entries).unwrap();
+    block.define_at(0, RloxValue::List(list));
+    block.define_at(1, RloxValue::Map(map));
+    let block_ctx = Rc::downgrade(&block.ctx);
+    drop(block);
+
+    assert!(block_ctx.upgrade().is_some());
+    Environment::collect_garbage();
+    assert!(block_ctx.upgrade().is_none());
+}
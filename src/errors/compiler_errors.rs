@@ -0,0 +1,38 @@
+use std::{error::Error, fmt::Display};
+
+use super::ReportError;
+use rlox_macros::{rlox_error, rlox_error_enum};
+
+#[derive(Debug)]
+#[rlox_error]
+pub struct LimitError {}
+
+#[derive(Debug)]
+#[rlox_error]
+pub struct StatementError {}
+
+#[derive(Debug)]
+#[rlox_error]
+pub struct UnsupportedError {}
+
+#[derive(Debug)]
+#[rlox_error_enum]
+pub enum CompilerError {
+    Limit(LimitError),
+    Statement(StatementError),
+    Unsupported(UnsupportedError),
+}
+
+impl CompilerError {
+    pub fn limit_error(line: usize, column: usize, msg: String) -> Self {
+        CompilerError::Limit(LimitError { line, column, msg })
+    }
+
+    pub fn statement_error(line: usize, column: usize, msg: String) -> Self {
+        CompilerError::Statement(StatementError { line, column, msg })
+    }
+
+    pub fn unsupported_error(line: usize, column: usize, msg: String) -> Self {
+        CompilerError::Unsupported(UnsupportedError { line, column, msg })
+    }
+}
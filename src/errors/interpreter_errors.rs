@@ -1,6 +1,7 @@
 use std::{error::Error, fmt::Display};
 
 use super::ReportError;
+use crate::token::RloxValue;
 use rlox_macros::{rlox_error, rlox_error_enum};
 
 #[derive(Debug)]
@@ -11,18 +12,58 @@ pub struct ValueError {}
 #[rlox_error]
 pub struct ExpresssionError {}
 
+#[derive(Debug)]
+#[rlox_error]
+pub struct OperandError {}
+
+#[derive(Debug)]
+#[rlox_error]
+pub struct ZeroDivisionError {}
+
+/// A value raised by `throw` that no `catch` handled yet.
+#[derive(Debug)]
+#[rlox_error]
+pub struct ThrownError {
+    pub value: RloxValue,
+}
+
+#[derive(Debug)]
 #[rlox_error_enum]
 pub enum RuntimeError {
     InvalidValue(ValueError),
     InvalidExpression(ExpresssionError),
+    InvalidOperand(OperandError),
+    ZeroDivision(ZeroDivisionError),
+    Thrown(ThrownError),
 }
 
 impl RuntimeError {
     pub fn value_error(line: usize, column: usize, msg: String) -> Self {
-        RuntimeError::InvalidValue(ValueError { msg, line, column })
+        RuntimeError::InvalidValue(ValueError { line, column, msg })
     }
 
     pub fn expression_error(line: usize, column: usize, msg: String) -> Self {
         RuntimeError::InvalidExpression(ExpresssionError { line, column, msg })
     }
+
+    pub fn operand_error(line: usize, column: usize, msg: String) -> Self {
+        RuntimeError::InvalidOperand(OperandError { line, column, msg })
+    }
+
+    pub fn zero_division_error(line: usize, column: usize) -> Self {
+        RuntimeError::ZeroDivision(ZeroDivisionError {
+            line,
+            column,
+            msg: "division by zero".to_string(),
+        })
+    }
+
+    pub fn thrown_error(line: usize, column: usize, msg: String, value: RloxValue) -> Self {
+        RuntimeError::Thrown(ThrownError {
+            value,
+            line,
+            column,
+            msg,
+        })
+    }
 }
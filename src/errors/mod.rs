@@ -1,3 +1,12 @@
+use std::{error::Error, fmt::Display};
+
+use compiler_errors::CompilerError;
+use interpreter_errors::RuntimeError;
+use parser_errors::ParserError;
+use resolver_errors::ResolverError;
+use scanner_errors::ScannerError;
+
+pub mod compiler_errors;
 pub mod interpreter_errors;
 pub mod parser_errors;
 pub mod resolver_errors;
@@ -8,3 +17,71 @@ pub trait ReportError {
     fn get_column(&self) -> usize;
     fn get_msg(&self) -> &str;
 }
+
+/// A call that was running when a runtime error was raised: the called
+/// function and where it was called from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "in {}, called at line {}, column {}",
+            self.function,
+            self.line + 1,
+            self.column + 1
+        )
+    }
+}
+
+/// Any error raised while running a piece of source, tagged with the stage
+/// that raised it. The parser reports every malformed statement it found,
+/// runtime errors come with the calls that led to them, innermost first.
+#[derive(Debug)]
+pub enum LoxError {
+    Scan(ScannerError),
+    Parse(Vec<ParserError>),
+    Resolve(ResolverError),
+    Compile(CompilerError),
+    Runtime(RuntimeError, Vec<StackFrame>),
+}
+
+impl LoxError {
+    /// The individual errors, in source order.
+    #[must_use]
+    pub fn errors(&self) -> Vec<&dyn ReportError> {
+        match self {
+            LoxError::Scan(e) => vec![e],
+            LoxError::Parse(errors) => errors.iter().map(|e| e as &dyn ReportError).collect(),
+            LoxError::Resolve(e) => vec![e],
+            LoxError::Compile(e) => vec![e],
+            LoxError::Runtime(e, _) => vec![e],
+        }
+    }
+}
+
+impl Error for LoxError {}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors = self.errors();
+        for (i, e) in errors.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "[{}:{}] {}",
+                e.get_line() + 1,
+                e.get_column() + 1,
+                e.get_msg()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::{error::Error, fmt::Display, usize};
+use std::{error::Error, fmt::Display};
 
 use super::ReportError;
 use rlox_macros::{rlox_error, rlox_error_enum};
@@ -17,6 +17,7 @@ pub struct NoLiteralValue {}
 
 #[derive(Debug)]
 #[rlox_error_enum]
+#[allow(clippy::enum_variant_names)]
 pub enum ParserError {
     ExprError(MalformedExpression),
     StmtError(MalformedStatement),
@@ -7,13 +7,23 @@ use rlox_macros::{rlox_error, rlox_error_enum};
 #[rlox_error]
 pub struct VariableError {}
 
+#[derive(Debug)]
+#[rlox_error]
+pub struct ClassError {}
+
+#[derive(Debug)]
 #[rlox_error_enum]
 pub enum ResolverError {
     Variable(VariableError),
+    Class(ClassError),
 }
 
 impl ResolverError {
     pub fn resolve_var_error(line: usize, column: usize, msg: String) -> Self {
         ResolverError::Variable(VariableError { line, column, msg })
     }
+
+    pub fn resolve_class_error(line: usize, column: usize, msg: String) -> Self {
+        ResolverError::Class(ClassError { line, column, msg })
+    }
 }
@@ -15,22 +15,34 @@ pub struct UnterminatedString {}
 #[rlox_error("unterminated comment")]
 pub struct UnterminatedComment {}
 
+/// A malformed escape sequence or number.
+#[derive(Debug)]
+#[rlox_error]
+pub struct InvalidLiteral {}
+
+#[derive(Debug)]
 #[rlox_error_enum]
+#[allow(clippy::enum_variant_names)]
 pub enum ScannerError {
     TokenError(InvalidToken),
     StringError(UnterminatedString),
     CommentError(UnterminatedComment),
+    LiteralError(InvalidLiteral),
 }
 
 impl ScannerError {
     pub fn invalid_token(line: usize, column: usize, msg: String) -> Self {
-        ScannerError::TokenError(InvalidToken { msg, line, column })
+        ScannerError::TokenError(InvalidToken { line, column, msg })
     }
 
     pub fn unterminated_string(line: usize, column: usize, msg: String) -> Self {
         ScannerError::StringError(UnterminatedString { line, column, msg })
     }
 
+    pub fn invalid_literal(line: usize, column: usize, msg: String) -> Self {
+        ScannerError::LiteralError(InvalidLiteral { line, column, msg })
+    }
+
     pub fn unterminated_comment(line: usize, column: usize, msg: String) -> Self {
         ScannerError::CommentError(UnterminatedComment { line, column, msg })
     }
@@ -1,7 +1,28 @@
-use std::{collections::HashMap, mem};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
+#[allow(clippy::wildcard_imports)]
 use crate::{
-    ast::{expr::*, stmt::*}, callable::Callable, class::{FieldType, RloxClass, RloxInstance}, environment::Environment, errors::interpreter_errors::RuntimeError, token::{RloxValue, Token, TokenType}
+    ast::{expr::*, stmt::*},
+    callable::{Callable, NativeFunction},
+    class::{ClassFunctions, FieldType, RloxClass, RloxInstance, RloxTrait},
+    environment::Environment,
+    errors::{interpreter_errors::RuntimeError, LoxError, ReportError, StackFrame},
+    list::RloxList,
+    map::RloxMap,
+    module::{Modules, RloxModule},
+    natives,
+    resolver::Local,
+    session,
+    token::{RloxValue, Token, TokenType},
+    vm::MAX_FRAMES,
 };
 
 enum ControlFlow {
@@ -21,38 +42,96 @@ impl From<RuntimeError> for RuntimeState {
     }
 }
 
-impl <'a>From<ControlFlow> for RuntimeState {
+impl From<ControlFlow> for RuntimeState {
     fn from(value: ControlFlow) -> Self {
         RuntimeState::Cf(value)
     }
 }
 
+/// What a `for in` loop walks through.
+enum ForInIter {
+    Values(std::vec::IntoIter<RloxValue>),
+    Iterator(RloxInstance),
+}
+
 type EvalExprResult = Result<RloxValue, RuntimeState>;
 type EvalStmtResult = Result<(), RuntimeState>;
 
 pub struct Interpreter {
+    globals: Environment,
     current_env: Environment,
-    locals: HashMap<usize, usize>,
+    /// The resolutions of every file of the program, shared with the
+    /// interpreters that run modules, like `output`, `modules` and
+    /// `error_class`.
+    locals: Rc<RefCell<HashMap<usize, Local>>>,
+    output: Rc<RefCell<Box<dyn Write>>>,
+    modules: Rc<RefCell<Modules>>,
+    /// The class of the values that caught runtime errors are bound to.
+    error_class: Rc<RloxClass>,
+    /// The directory imports are relative to.
+    dir: PathBuf,
+    /// Calls being run, outermost first.
+    frames: Vec<StackFrame>,
+    /// The calls that were running when the last runtime error was raised.
+    traceback: Vec<StackFrame>,
 }
 
 impl Interpreter {
-    pub fn new(env: Environment, locals: HashMap<usize, usize>) -> Self {
+    pub fn new(mut env: Environment, locals: HashMap<usize, Local>) -> Self {
+        natives::define_natives(&mut env);
+        let error_class = RloxClass::new(
+            "Error".to_string(),
+            None,
+            ClassFunctions::default(),
+            Vec::new(),
+            Vec::new(),
+            env.clone(),
+            Vec::new(),
+        );
         Interpreter {
+            globals: env.clone(),
             current_env: env,
-            locals,
+            locals: Rc::new(RefCell::new(locals)),
+            output: Rc::new(RefCell::new(Box::new(io::stdout()))),
+            modules: Rc::new(RefCell::new(Modules::default())),
+            error_class: Rc::new(error_class),
+            dir: PathBuf::new(),
+            frames: Vec::new(),
+            traceback: Vec::new(),
         }
     }
 
+    /// Takes the calls that led to the last runtime error, innermost first.
+    pub fn take_traceback(&mut self) -> Vec<StackFrame> {
+        mem::take(&mut self.traceback)
+    }
+
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Rc::new(RefCell::new(output));
+    }
+
+    pub fn define_global(&mut self, name: &str, value: RloxValue) {
+        self.globals.define_var(name.to_string(), value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<RloxValue> {
+        self.globals.get_var(name)
+    }
+
+    #[allow(clippy::unused_self)]
     fn value_error(&self, message: &str, token: &Token) -> RuntimeState {
         let e = RuntimeError::value_error(token.line, token.column, message.to_string());
         RuntimeState::RtErr(e)
     }
 
+    #[allow(clippy::unused_self)]
     fn expression_error(&self, message: &str, token: &Token) -> RuntimeState {
         let e = RuntimeError::expression_error(token.line, token.column, message.to_string());
         RuntimeState::RtErr(e)
     }
 
+    #[allow(clippy::unused_self)]
     fn is_truthy(&self, value: &RloxValue) -> bool {
         match value {
             RloxValue::Bool(b) => *b,
@@ -61,18 +140,22 @@ impl Interpreter {
         }
     }
 
+    #[allow(clippy::unused_self, clippy::float_cmp)]
     fn is_equal(&self, v1: &RloxValue, v2: &RloxValue) -> bool {
         match (v1, v2) {
             (RloxValue::Nil, RloxValue::Nil) => true,
-            (RloxValue::Nil, _) => false,
-            (_, RloxValue::Nil) => false,
             (RloxValue::Num(n1), RloxValue::Num(n2)) => n1 == n2,
             (RloxValue::Str(s1), RloxValue::Str(s2)) => s1 == s2,
             (RloxValue::Bool(b1), RloxValue::Bool(b2)) => b1 == b2,
+            (RloxValue::List(l1), RloxValue::List(l2)) => l1.ptr_eq(l2),
+            (RloxValue::Map(m1), RloxValue::Map(m2)) => m1.ptr_eq(m2),
+            (RloxValue::Class(c1), RloxValue::Class(c2)) => Rc::ptr_eq(c1, c2),
+            (RloxValue::Instance(i1), RloxValue::Instance(i2)) => i1.ptr_eq(i2),
             _ => false,
         }
     }
 
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
     fn eval_literal(&self, expr: &Literal) -> EvalExprResult {
         Ok(expr.value.convert())
     }
@@ -85,107 +168,184 @@ impl Interpreter {
         let right = self.evaluate(&expr.right)?;
 
         Ok(match expr.operator.r#type {
-            TokenType::Minus => match right {
-                RloxValue::Num(n) => RloxValue::Num(-n),
-                _ => RloxValue::Nil,
-            },
-            TokenType::Bang => RloxValue::Bool(self.is_truthy(&right)),
+            TokenType::Minus => {
+                if let Some(v) = self.call_operator(&right, "__neg__", &[], &expr.operator)? {
+                    return Ok(v);
+                }
+                let RloxValue::Num(n) = right else {
+                    let msg = format!(
+                        "operand of '-' must be a number, found {}",
+                        right.type_name()
+                    );
+                    return Err(self.operand_error(&msg, &expr.operator));
+                };
+                RloxValue::Num(-n)
+            }
+            TokenType::Bang => RloxValue::Bool(!self.is_truthy(&right)),
             _ => RloxValue::Nil,
         })
     }
 
+    #[allow(clippy::unused_self)]
+    fn operand_error(&self, message: &str, token: &Token) -> RuntimeState {
+        let e = RuntimeError::operand_error(token.line, token.column, message.to_string());
+        RuntimeState::RtErr(e)
+    }
+
+    fn number_operands(
+        &self,
+        operator: &Token,
+        left: &RloxValue,
+        right: &RloxValue,
+    ) -> Result<(f64, f64), RuntimeState> {
+        if let (RloxValue::Num(n1), RloxValue::Num(n2)) = (left, right) {
+            return Ok((*n1, *n2));
+        }
+        let msg = format!(
+            "operands of '{}' must be numbers, found {} and {}",
+            operator.lexme,
+            left.type_name(),
+            right.type_name()
+        );
+        Err(self.operand_error(&msg, operator))
+    }
+
+    /// Calls the method overloading an operator, `None` if `value` is not an
+    /// instance of a class defining `name`.
+    fn call_operator(
+        &mut self,
+        value: &RloxValue,
+        name: &str,
+        args: &[RloxValue],
+        operator: &Token,
+    ) -> Result<Option<RloxValue>, RuntimeState> {
+        let RloxValue::Instance(instance) = value else {
+            return Ok(None);
+        };
+        match instance.bound_method(name) {
+            Some(method) => self.call(args, &method, operator).map(Some),
+            None => Ok(None),
+        }
+    }
+
     fn eval_binary(&mut self, expr: &Binary) -> EvalExprResult {
         let left = self.evaluate(&expr.left)?;
         let right = self.evaluate(&expr.right)?;
+        let operator = &expr.operator;
 
-        Ok(match expr.operator.r#type {
-            TokenType::Minus => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Num(n1 - n2),
-                _ => RloxValue::Nil,
-            },
-            TokenType::Slash => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Num(n1 / n2),
-                _ => RloxValue::Nil,
-            },
-            TokenType::Star => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Num(n1 * n2),
-                _ => RloxValue::Nil,
-            },
-            TokenType::Plus => match (left, right) {
+        let method = match operator.r#type {
+            TokenType::Plus => Some("__add__"),
+            TokenType::Minus => Some("__sub__"),
+            TokenType::Star => Some("__mul__"),
+            TokenType::Slash => Some("__div__"),
+            TokenType::Less => Some("__lt__"),
+            TokenType::LessEqual => Some("__le__"),
+            TokenType::Greater => Some("__gt__"),
+            TokenType::GreaterEqual => Some("__ge__"),
+            TokenType::EqualEqual | TokenType::BangEqual => Some("__eq__"),
+            _ => None,
+        };
+        if let Some(method) = method {
+            let overloaded =
+                self.call_operator(&left, method, std::slice::from_ref(&right), operator)?;
+            if let Some(v) = overloaded {
+                return Ok(match operator.r#type {
+                    TokenType::EqualEqual => RloxValue::Bool(self.is_truthy(&v)),
+                    TokenType::BangEqual => RloxValue::Bool(!self.is_truthy(&v)),
+                    _ => v,
+                });
+            }
+        }
+
+        Ok(match operator.r#type {
+            TokenType::Minus => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                RloxValue::Num(n1 - n2)
+            }
+            TokenType::Slash => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                if n2 == 0.0 {
+                    let e = RuntimeError::zero_division_error(operator.line, operator.column);
+                    return Err(e.into());
+                }
+                RloxValue::Num(n1 / n2)
+            }
+            TokenType::Star => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                RloxValue::Num(n1 * n2)
+            }
+            TokenType::Plus => match (&left, &right) {
                 (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Num(n1 + n2),
-                (RloxValue::Str(s1), RloxValue::Str(s2)) => RloxValue::Str(format!("{}{}", s1, s2)),
-                _ => RloxValue::Nil,
-            },
-            TokenType::Greater => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Bool(n1 > n2),
-                _ => RloxValue::Nil,
-            },
-            TokenType::GreaterEqual => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Bool(n1 >= n2),
-                _ => RloxValue::Nil,
-            },
-            TokenType::Less => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Bool(n1 < n2),
-                _ => RloxValue::Nil,
-            },
-            TokenType::LessEqual => match (left, right) {
-                (RloxValue::Num(n1), RloxValue::Num(n2)) => RloxValue::Bool(n1 <= n2),
-                _ => RloxValue::Nil,
+                (RloxValue::Str(s1), RloxValue::Str(s2)) => RloxValue::Str(format!("{s1}{s2}")),
+                _ => {
+                    let msg = format!(
+                        "operands of '+' must be two numbers or two strings, found {} and {}",
+                        left.type_name(),
+                        right.type_name()
+                    );
+                    return Err(self.operand_error(&msg, operator));
+                }
             },
+            TokenType::Greater => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                RloxValue::Bool(n1 > n2)
+            }
+            TokenType::GreaterEqual => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                RloxValue::Bool(n1 >= n2)
+            }
+            TokenType::Less => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                RloxValue::Bool(n1 < n2)
+            }
+            TokenType::LessEqual => {
+                let (n1, n2) = self.number_operands(operator, &left, &right)?;
+                RloxValue::Bool(n1 <= n2)
+            }
             TokenType::BangEqual => RloxValue::Bool(!self.is_equal(&left, &right)),
             TokenType::EqualEqual => RloxValue::Bool(self.is_equal(&left, &right)),
             _ => RloxValue::Nil,
         })
     }
 
-    fn look_up_variable(&mut self, name: &Token, expr: &Variable) -> EvalExprResult {
-        match self.locals.get(&expr.id) {
-            Some(d) => {
-                let res = self.current_env.get_at(*d, name.lexme.clone());
-                match res {
-                    Ok(value) => match value {
-                        Some(v) => Ok(v.clone()),
-                        None => Ok(RloxValue::Nil),
-                    },
-                    Err(_) => Ok(RloxValue::Nil),
-                }
-            }
-            None => match self.current_env.get_var(&name.lexme) {
-                Some(v) => Ok(v.clone()),
-                None => Ok(RloxValue::Nil),
-            },
+    /// The value of a variable, `None` if it is not defined.
+    fn local(&self, id: usize) -> Option<Local> {
+        self.locals.borrow().get(&id).copied()
+    }
+
+    fn variable_value(&self, name: &Token, id: usize) -> Option<RloxValue> {
+        match self.local(id) {
+            Some(l) => self.current_env.get_at(l.depth, l.slot).ok().flatten(),
+            None => self.globals.get_var(&name.lexme),
         }
     }
 
+    #[allow(clippy::unnecessary_wraps)]
+    fn look_up_variable(&mut self, name: &Token, id: usize) -> EvalExprResult {
+        Ok(self.variable_value(name, id).unwrap_or(RloxValue::Nil))
+    }
+
     fn eval_variable(&mut self, expr: &Variable) -> EvalExprResult {
-        self.look_up_variable(&expr.name, expr)
+        self.look_up_variable(&expr.name, expr.id)
     }
 
     fn eval_assign(&mut self, expr: &Assign) -> EvalExprResult {
         let var_name = &expr.name.lexme;
-        let check = self.current_env.check(var_name);
-        if !check {
-            return Err(self.value_error(
-                &format!("cannot find variable {} in this scope", var_name),
-                &expr.name,
-            ));
-        }
         let value = self.evaluate(&expr.value)?;
 
-        let distance = self.locals.get(&expr.id);
-        match distance {
-            Some(d) => {
-                if let Err(_) = self.current_env.assign_at(*d, var_name.clone(), value) {
+        match self.local(expr.id) {
+            Some(l) => {
+                if let Err(()) = self.current_env.assign_at(l.depth, l.slot, value) {
                     return Err(self.value_error(
-                        &format!("cannot assign value to {} in this scope", var_name),
+                        &format!("cannot assign value to {var_name} in this scope"),
                         &expr.name,
                     ));
                 }
             }
             None => {
-                if let Err(_) = self.current_env.assign_var(var_name.clone(), value) {
+                if let Err(()) = self.globals.assign_var(var_name, value) {
                     return Err(self.value_error(
-                        &format!("cannot assign value to {} in this scope", var_name),
+                        &format!("cannot find variable {var_name} in this scope"),
                         &expr.name,
                     ));
                 }
@@ -219,121 +379,427 @@ impl Interpreter {
         }
     }
 
-    fn call(&mut self, args: &Vec<RloxValue>, callable: &Callable) -> EvalExprResult {
-        let mut ret_val = RloxValue::Nil;
-        let mut env = Environment::new();
-        env.add_enclosing(&self.current_env);
-        let prev = mem::replace(&mut self.current_env, env);
-        let fun_stmt = callable.clone().function;
+    fn call(&mut self, args: &[RloxValue], callable: &Callable, site: &Token) -> EvalExprResult {
+        let fun_stmt = callable.function.clone();
         let params_len = fun_stmt.params.len();
         let args_len = args.len();
         if params_len != args_len {
-            return Err(self.expression_error(format!("this function takes {} args but {} were given", params_len, args_len).as_str(), &callable.function.name))
+            return Err(self.expression_error(
+                format!("this function takes {params_len} args but {args_len} were given").as_str(),
+                site,
+            ));
         }
-        for (i, param) in fun_stmt.params.iter().enumerate() {
-            self.current_env
-                .define_var(param.lexme.clone(), args[i].clone());
+        if self.frames.len() == MAX_FRAMES {
+            return Err(self.value_error("stack overflow", site));
+        }
+        let mut env = Environment::new();
+        env.add_enclosing(&callable.closure);
+        for (slot, arg) in args.iter().enumerate() {
+            env.define_at(slot, arg.clone());
         }
 
+        self.frames.push(StackFrame {
+            function: fun_stmt.name.lexme.clone(),
+            line: site.line,
+            column: site.column,
+        });
+        let prev = mem::replace(&mut self.current_env, env);
+        // the function sees the globals of the file that declared it
+        let prev_globals = mem::replace(&mut self.globals, callable.globals.clone());
+        let mut ret_val = Ok(RloxValue::Nil);
         for stmt in &fun_stmt.body {
             if let Err(e) = self.execute(stmt) {
                 match e {
-                    RuntimeState::Cf(c) => match c {
-                        ControlFlow::Return(v) => {
-                            ret_val = v;
+                    RuntimeState::Cf(c) => {
+                        if let ControlFlow::Return(v) = c {
+                            ret_val = Ok(v);
                             break;
                         }
-                        _ => (),
-                    },
-                    _ => return Err(e),
-                };
-            };
+                    }
+                    RuntimeState::RtErr(_) => {
+                        // the innermost call records the whole stack
+                        if self.traceback.is_empty() {
+                            self.traceback = self.frames.iter().rev().cloned().collect();
+                        }
+                        ret_val = Err(e);
+                        break;
+                    }
+                }
+            }
         }
+        self.frames.pop();
         self.current_env = prev;
-        return Ok(ret_val);
+        self.globals = prev_globals;
+        ret_val
     }
 
-    fn eval_call(&mut self, expr: &Call) -> EvalExprResult {
-        let mut args = Vec::new();
-        let name = expr.callee.clone();
-        for arg in &expr.args {
-            args.push(self.evaluate(&arg)?);
+    fn call_native(
+        &mut self,
+        args: &[RloxValue],
+        native: &NativeFunction,
+        paren: &Token,
+    ) -> EvalExprResult {
+        let args_len = args.len();
+        if native.arity != args_len {
+            return Err(self.expression_error(
+                format!(
+                    "this function takes {} args but {args_len} were given",
+                    native.arity
+                )
+                .as_str(),
+                paren,
+            ));
         }
+        let result = if native.formats_args {
+            let mut formatted = Vec::with_capacity(args_len);
+            for arg in args {
+                formatted.push(RloxValue::Str(self.stringify(arg)?));
+            }
+            (native.function)(&formatted)
+        } else {
+            (native.function)(args)
+        };
+        result.map_err(|message| self.value_error(&message, paren))
+    }
 
-        if let Some(&ref val) = self.current_env.get_var(&name) {
-            match val {
-                RloxValue::Callable(c) => return self.call(&args, c),
-                _ => {
+    fn eval_call(&mut self, expr: &Call) -> EvalExprResult {
+        let callee = match expr.callee.as_ref() {
+            Expr::Variable(v) => {
+                let Some(value) = self.variable_value(&v.name, v.id) else {
                     return Err(self.value_error(
-                        &format!("cannot find function {} in this scope", name),
+                        &format!("cannot find function {} in this scope", v.name.lexme),
                         &expr.paren,
-                    ))
-                }
+                    ));
+                };
+                value
             }
-        }
+            callee => self.evaluate(callee)?,
+        };
+        let args = self.eval_args(&expr.args)?;
+        self.call_value(callee, &args, &expr.paren)
+    }
 
-        if let Some(class) = self.current_env.get_class(&name) {
-            let instance = RloxInstance::new(class.clone(), args);
+    /// Creates an instance of `class`. Fields get their defaults, from the
+    /// superclasses down, then `init()` or the parameter list takes `args`.
+    fn instantiate(
+        &mut self,
+        class: &Rc<RloxClass>,
+        args: &[RloxValue],
+        site: &Token,
+    ) -> EvalExprResult {
+        let mut fields = HashMap::new();
+        self.field_defaults(class, &mut fields)?;
+        if let Some(init) = class.find_method("init") {
+            let instance = RloxInstance::new(class.clone(), fields);
+            self.call(args, &init.bind(&instance), site)?;
             return Ok(RloxValue::Instance(instance));
         }
+        if args.len() != class.params.len() {
+            return Err(self.expression_error(
+                &format!(
+                    "{} takes {} args but {} were given",
+                    class.name,
+                    class.params.len(),
+                    args.len()
+                ),
+                site,
+            ));
+        }
+        fields.extend(class.params.iter().cloned().zip(args.iter().cloned()));
+        Ok(RloxValue::Instance(RloxInstance::new(
+            class.clone(),
+            fields,
+        )))
+    }
 
-        Err(self.value_error(
-            &format!("cannot find function {} in this scope", name),
-            &expr.paren,
-        ))
+    fn field_defaults(
+        &mut self,
+        class: &RloxClass,
+        fields: &mut HashMap<String, RloxValue>,
+    ) -> Result<(), RuntimeState> {
+        if let Some(superclass) = &class.superclass {
+            self.field_defaults(superclass, fields)?;
+        }
+        let prev = mem::replace(&mut self.current_env, class.closure.clone());
+        let prev_globals = mem::replace(&mut self.globals, class.closure.root());
+        let result = class.fields.iter().try_for_each(|field| {
+            let value = self.evaluate(&field.initializer)?;
+            fields.insert(field.name.lexme.clone(), value);
+            Ok(())
+        });
+        self.current_env = prev;
+        self.globals = prev_globals;
+        result
     }
 
-    fn call_method(&mut self, instance: &RloxInstance, method: &Callable, method_args: &Option<Vec<Expr>>) -> EvalExprResult {
-        let mut env = Environment::new();
-        env.add_enclosing(&self.current_env);
-        env.define_var("this".to_string(), RloxValue::Instance(instance.clone()));
-        let prev = mem::replace(&mut self.current_env, env);
-        let mut args = Vec::new();
-        if let Some(m_args) = method_args {
-            for arg in m_args {
-                args.push(self.evaluate(&arg)?);
+    fn eval_args(&mut self, args: &[Expr]) -> Result<Vec<RloxValue>, RuntimeState> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.evaluate(arg)?);
+        }
+        Ok(values)
+    }
+
+    /// Calls `callee` with `args`, `site` is the call's position.
+    fn call_value(
+        &mut self,
+        callee: RloxValue,
+        args: &[RloxValue],
+        site: &Token,
+    ) -> EvalExprResult {
+        match callee {
+            RloxValue::Callable(c) => self.call(args, &c, site),
+            RloxValue::NativeFunction(n) => self.call_native(args, &n, site),
+            RloxValue::Class(c) => self.instantiate(&c, args, site),
+            v => Err(self.value_error(
+                &format!(
+                    "can only call functions and classes, found {}",
+                    v.type_name()
+                ),
+                site,
+            )),
+        }
+    }
+
+    /// Calls `method` on `instance`, or returns it bound to the instance if
+    /// there is no argument list.
+    fn call_method(
+        &mut self,
+        instance: &RloxInstance,
+        method: &Callable,
+        method_args: Option<&Vec<Expr>>,
+        site: &Token,
+    ) -> EvalExprResult {
+        let bound = method.bind(instance);
+        match method_args {
+            Some(m_args) => {
+                let args = self.eval_args(m_args)?;
+                self.call(&args, &bound, site)
+            }
+            None => Ok(RloxValue::Callable(bound)),
+        }
+    }
+
+    fn eval_interpolation(&mut self, expr: &Interpolation) -> EvalExprResult {
+        let mut text = String::new();
+        for part in &expr.parts {
+            match self.evaluate(part)? {
+                RloxValue::Str(s) => text.push_str(&s),
+                value => text.push_str(&self.stringify(&value)?),
+            }
+        }
+        Ok(RloxValue::Str(text))
+    }
+
+    fn eval_list(&mut self, expr: &List) -> EvalExprResult {
+        let mut elements = Vec::with_capacity(expr.elements.len());
+        for element in &expr.elements {
+            elements.push(self.evaluate(element)?);
+        }
+        Ok(RloxValue::List(RloxList::new(elements)))
+    }
+
+    fn eval_map(&mut self, expr: &Map) -> EvalExprResult {
+        let mut entries = Vec::with_capacity(expr.entries.len());
+        for (key, value) in &expr.entries {
+            entries.push((self.evaluate(key)?, self.evaluate(value)?));
+        }
+        RloxMap::new(entries)
+            .map(RloxValue::Map)
+            .map_err(|message| self.value_error(&message, &expr.brace))
+    }
+
+    fn eval_index(&mut self, expr: &Index) -> EvalExprResult {
+        let object = self.evaluate(&expr.object)?;
+        let key = self.evaluate(&expr.key)?;
+        let result = match object {
+            RloxValue::List(l) => l.get(&key),
+            RloxValue::Map(m) => m.get(&key),
+            v => Err(format!(
+                "only lists and maps can be indexed, found {}",
+                v.type_name()
+            )),
+        };
+        result.map_err(|message| self.value_error(&message, &expr.bracket))
+    }
+
+    fn eval_set_index(&mut self, expr: &SetIndex) -> EvalExprResult {
+        let object = self.evaluate(&expr.object)?;
+        let key = self.evaluate(&expr.key)?;
+        let value = self.evaluate(&expr.value)?;
+        let result = match object {
+            RloxValue::List(l) => l.set(&key, value),
+            RloxValue::Map(m) => m.set(&key, value),
+            v => Err(format!(
+                "only lists and maps can be indexed, found {}",
+                v.type_name()
+            )),
+        };
+        result.map_err(|message| self.value_error(&message, &expr.bracket))?;
+        Ok(RloxValue::Nil)
+    }
+
+    /// Calls a method of a built-in type, `call_method` runs the method and
+    /// returns `None` if there is no method with that name.
+    fn call_builtin_method(
+        &mut self,
+        type_name: &str,
+        expr: &Get,
+        call_method: impl Fn(&str, &[RloxValue]) -> Option<Result<RloxValue, String>>,
+    ) -> EvalExprResult {
+        let name = &expr.name.lexme;
+        let Some(method_args) = &expr.method_args else {
+            return Err(self.value_error(
+                &format!("{type_name} method {name} must be called"),
+                &expr.name,
+            ));
+        };
+        let mut args = Vec::with_capacity(method_args.len());
+        for arg in method_args {
+            args.push(self.evaluate(arg)?);
+        }
+        match call_method(name, &args) {
+            Some(result) => result.map_err(|message| self.value_error(&message, &expr.name)),
+            None => {
+                Err(self.value_error(&format!("undefined {type_name} method {name}"), &expr.name))
             }
         }
-        let ret_val = self.call(&args, method);
-        self.current_env = prev;
-        ret_val
     }
 
     fn eval_get(&mut self, expr: &Get) -> EvalExprResult {
         let object = self.evaluate(&expr.object)?;
-        let args = &expr.method_args;
-        match object {
-            RloxValue::Instance(i) => {
-                match i.get(&expr.name.lexme) {
-                    Some(v) => match v {
-                        FieldType::Field(f) => Ok(f.clone()),
-                        FieldType::Method(m) => self.call_method(&i, &m, args),
-                    },
-                    None => Err(self.value_error("undefined property", &expr.name)),
-                }
+        let name = &expr.name.lexme;
+        let (this, property) = match object {
+            RloxValue::List(l) => {
+                return self.call_builtin_method("list", expr, |n, a| l.call_method(n, a))
+            }
+            RloxValue::Map(m) => {
+                return self.call_builtin_method("map", expr, |n, a| m.call_method(n, a))
             }
+            RloxValue::Instance(i) => match i.get(name) {
+                Some(p) => (RloxValue::Instance(i), p),
+                None => return Err(self.value_error("undefined property", &expr.name)),
+            },
+            RloxValue::Module(m) => match m.get(name) {
+                Some(v) => (RloxValue::Module(m), FieldType::Field(v)),
+                None => {
+                    return Err(self.value_error(
+                        &format!("module \"{}\" has no export {name}", m.name),
+                        &expr.name,
+                    ))
+                }
+            },
+            RloxValue::Class(c) => match c.get_static(name) {
+                Some(p) => (RloxValue::Class(c), p),
+                None => {
+                    return Err(
+                        self.value_error(&format!("undefined static member {name}"), &expr.name)
+                    )
+                }
+            },
             _ => return Err(self.value_error("only instances have properties", &expr.name)),
+        };
+        let value = match property {
+            FieldType::Field(f) => f,
+            FieldType::Method(m) => RloxValue::Callable(m.bind_this(this)),
+            FieldType::Getter(g) => self.call(&[], &g.bind_this(this), &expr.name)?,
+            FieldType::Setter(_) => {
+                return Err(self.value_error(
+                    &format!("can't read {name}, it only has a setter"),
+                    &expr.name,
+                ))
+            }
+        };
+        match &expr.method_args {
+            Some(args) => {
+                let args = self.eval_args(args)?;
+                self.call_value(value, &args, &expr.name)
+            }
+            None => Ok(value),
         }
     }
 
     fn eval_set(&mut self, expr: &Set) -> EvalExprResult {
         let object = self.evaluate(&expr.object)?;
+        let name = &expr.name.lexme;
         match object {
             RloxValue::Instance(mut i) => {
                 let value = self.evaluate(&expr.value)?;
-                return match i.set(expr.name.lexme.clone(), value) {
-                    Some(v) => Ok(v),
-                    None => Err(self.value_error("only instances have properties", &expr.name)),
-                };
+                // like a variable assignment, setting a property gives nil
+                match i.set(name, value.clone()) {
+                    Some(FieldType::Setter(s)) => {
+                        self.call(&[value], &s.bind(&i), &expr.name)?;
+                        Ok(RloxValue::Nil)
+                    }
+                    Some(FieldType::Field(_)) => Ok(RloxValue::Nil),
+                    _ if i.has_getter(name) => Err(self.value_error(
+                        &format!("can't assign to {name}, it only has a getter"),
+                        &expr.name,
+                    )),
+                    _ => Err(self.value_error("undefined property", &expr.name)),
+                }
             }
-            _ => return Err(self.value_error("only instances have properties", &expr.name)),
+            RloxValue::Class(c) => {
+                let value = self.evaluate(&expr.value)?;
+                match c.set_static(name, value) {
+                    Some(_) => Ok(RloxValue::Nil),
+                    None => {
+                        Err(self.value_error(&format!("undefined static field {name}"), &expr.name))
+                    }
+                }
+            }
+            _ => Err(self.value_error("only instances have properties", &expr.name)),
         }
     }
 
     fn eval_this(&mut self, expr: &This) -> EvalExprResult {
-        match self.current_env.get_var("this") {
-            Some(v) => Ok(v.clone()),
-            None => Err(self.value_error("only instances have properties", &expr.keyword))
+        let this = match self.local(expr.id) {
+            Some(l) => self.current_env.get_at(l.depth, l.slot).ok().flatten(),
+            None => None,
+        };
+        match this {
+            Some(v) => Ok(v),
+            None => Err(self.value_error("only instances have properties", &expr.keyword)),
+        }
+    }
+
+    fn eval_super(&mut self, expr: &Super) -> EvalExprResult {
+        let Some(local) = self.local(expr.id) else {
+            return Err(self.value_error(
+                "can't use \"super\" in a class with no superclass",
+                &expr.keyword,
+            ));
+        };
+        let superclass = self
+            .current_env
+            .get_at(local.depth, local.slot)
+            .ok()
+            .flatten();
+        let Some(RloxValue::Class(superclass)) = superclass else {
+            return Err(self.value_error(
+                "can't use \"super\" in a class with no superclass",
+                &expr.keyword,
+            ));
+        };
+        // `this` is bound in the scope right inside the one holding `super`
+        let this = match local.depth {
+            0 => None,
+            depth => self.current_env.get_at(depth - 1, 0).ok().flatten(),
+        };
+        let Some(RloxValue::Instance(instance)) = this else {
+            return Err(self.value_error("can't use \"super\" outside of a method", &expr.keyword));
+        };
+        match superclass.find_method(&expr.method.lexme) {
+            Some(m) => self.call_method(
+                &instance,
+                &m.clone(),
+                expr.method_args.as_ref(),
+                &expr.method,
+            ),
+            None => Err(self.value_error(
+                &format!("undefined superclass method {}", expr.method.lexme),
+                &expr.method,
+            )),
         }
     }
 
@@ -349,7 +815,18 @@ impl Interpreter {
             Expr::Call(c) => self.eval_call(c),
             Expr::Get(g) => self.eval_get(g),
             Expr::Set(s) => self.eval_set(s),
-            Expr::This(t) => self.eval_this(t)
+            Expr::This(t) => self.eval_this(t),
+            Expr::Super(s) => self.eval_super(s),
+            Expr::List(l) => self.eval_list(l),
+            Expr::Interpolation(i) => self.eval_interpolation(i),
+            Expr::Map(m) => self.eval_map(m),
+            Expr::Lambda(l) => Ok(RloxValue::Callable(Callable::new(
+                l.function.clone(),
+                self.current_env.clone(),
+                self.globals.clone(),
+            ))),
+            Expr::Index(i) => self.eval_index(i),
+            Expr::SetIndex(s) => self.eval_set_index(s),
         }
     }
 
@@ -360,13 +837,24 @@ impl Interpreter {
 
     fn eval_print_stmt(&mut self, stmt: &ExprStmt) -> EvalStmtResult {
         let value = self.evaluate(&stmt.expr)?;
-        println!("{}", value.to_string());
+        let text = self.stringify(&value)?;
+        // like a closed stdout, a failing writer does not stop the program
+        writeln!(self.output.borrow_mut(), "{text}").ok();
         Ok(())
     }
 
+    /// Defines a declaration in the current scope, in its resolved slot if it
+    /// is a local or by name if it is a global.
+    fn define(&mut self, id: usize, name: &Token, value: RloxValue) {
+        match self.local(id) {
+            Some(l) => self.current_env.define_at(l.slot, value),
+            None => self.current_env.define_var(name.lexme.clone(), value),
+        }
+    }
+
     fn define_var_stmt(&mut self, stmt: &VarStmt) -> EvalStmtResult {
         let value = self.evaluate(&stmt.initializer)?;
-        self.current_env.define_var(stmt.name.lexme.clone(), value);
+        self.define(stmt.id, &stmt.name, value);
         Ok(())
     }
 
@@ -374,21 +862,17 @@ impl Interpreter {
         let mut new_env = Environment::new();
         new_env.add_enclosing(&self.current_env);
         let prev = mem::replace(&mut self.current_env, new_env);
-        for s in &stmt.statements {
-            self.execute(s)?;
-        }
-        let _ = mem::replace(&mut self.current_env, prev);
-        Ok(())
+        let result = stmt.statements.iter().try_for_each(|s| self.execute(s));
+        self.current_env = prev;
+        result
     }
 
     fn execute_if_stmt(&mut self, stmt: &IfStmt) -> EvalStmtResult {
         let condition = self.evaluate(&stmt.condition)?;
         if self.is_truthy(&condition) {
             self.execute(&stmt.then_branch)?;
-        } else {
-            if let Some(else_stmt) = &stmt.else_branch {
-                self.execute(&else_stmt)?;
-            }
+        } else if let Some(else_stmt) = &stmt.else_branch {
+            self.execute(else_stmt)?;
         }
         Ok(())
     }
@@ -402,50 +886,159 @@ impl Interpreter {
                     RuntimeState::Cf(c) => match c {
                         ControlFlow::Break => break,
                         ControlFlow::Continue => (),
-                        _ => return Err(e),
+                        ControlFlow::Return(_) => return Err(e),
                     },
-                    _ => return Err(e),
+                    RuntimeState::RtErr(_) => return Err(e),
                 }
-            };
+            }
             condition = self.evaluate(&stmt.condition)?;
         }
         Ok(())
     }
 
     fn execute_for_stmt(&mut self, stmt: &ForStmt) -> EvalStmtResult {
+        let mut new_env = Environment::new();
+        new_env.add_enclosing(&self.current_env);
+        let prev = mem::replace(&mut self.current_env, new_env);
+        let result = self.execute_for_loop(stmt);
+        self.current_env = prev;
+        result
+    }
+
+    fn execute_for_loop(&mut self, stmt: &ForStmt) -> EvalStmtResult {
         if let Some(i) = &stmt.initializer {
             match &i {
                 ForStmtInitializer::VarDecl(v) => self.define_var_stmt(v)?,
                 ForStmtInitializer::ExprStmt(e) => self.eval_expression_stmt(e)?,
-            };
+            }
         }
-        if let Some(c) = &stmt.condition {
-            let mut condition = self.evaluate(c)?;
-            while self.is_truthy(&condition) {
-                let body = &stmt.body;
-                if let Err(e) = self.execute(body) {
-                    match &e {
-                        RuntimeState::Cf(c) => match c {
-                            ControlFlow::Break => break,
-                            ControlFlow::Continue => (),
-                            _ => return Err(e),
-                        },
-                        _ => return Err(e),
-                    }
-                };
-                if let Some(a) = &stmt.afterthought {
-                    self.evaluate(a)?;
+        loop {
+            // a missing condition loops until a break or return
+            if let Some(c) = &stmt.condition {
+                let condition = self.evaluate(c)?;
+                if !self.is_truthy(&condition) {
+                    break;
+                }
+            }
+            if let Err(e) = self.execute(&stmt.body) {
+                match &e {
+                    RuntimeState::Cf(c) => match c {
+                        ControlFlow::Break => break,
+                        ControlFlow::Continue => (),
+                        ControlFlow::Return(_) => return Err(e),
+                    },
+                    RuntimeState::RtErr(_) => return Err(e),
                 }
-                condition = self.evaluate(c)?;
+            }
+            if let Some(a) = &stmt.afterthought {
+                self.evaluate(a)?;
             }
         }
         Ok(())
     }
 
+    fn execute_for_in_stmt(&mut self, stmt: &ForInStmt) -> EvalStmtResult {
+        let iterable = self.evaluate(&stmt.iterable)?;
+        let mut iter = self.for_in_iter(iterable, &stmt.keyword)?;
+        while let Some(value) = self.next_item(&mut iter, &stmt.keyword)? {
+            // a fresh scope per iteration, closures keep their own value
+            let mut new_env = Environment::new();
+            new_env.add_enclosing(&self.current_env);
+            let prev = mem::replace(&mut self.current_env, new_env);
+            self.define(stmt.id, &stmt.name, value);
+            let result = self.execute(&stmt.body);
+            self.current_env = prev;
+            if let Err(e) = result {
+                match &e {
+                    RuntimeState::Cf(c) => match c {
+                        ControlFlow::Break => break,
+                        ControlFlow::Continue => (),
+                        ControlFlow::Return(_) => return Err(e),
+                    },
+                    RuntimeState::RtErr(_) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists and maps are iterated over a snapshot of their elements or keys,
+    /// instances through the iterator returned by their `iter()` method.
+    fn for_in_iter(
+        &mut self,
+        iterable: RloxValue,
+        keyword: &Token,
+    ) -> Result<ForInIter, RuntimeState> {
+        let values = match iterable {
+            RloxValue::Str(s) => s.chars().map(|c| RloxValue::Str(c.to_string())).collect(),
+            RloxValue::List(l) => l.items(),
+            RloxValue::Map(m) => m.keys(),
+            RloxValue::Instance(i) => {
+                return match self.iterator_property(&i, "iter", keyword)? {
+                    RloxValue::Instance(iterator) => Ok(ForInIter::Iterator(iterator)),
+                    v => Err(self.value_error(
+                        &format!("iter() must return an instance, found {}", v.type_name()),
+                        keyword,
+                    )),
+                };
+            }
+            v => {
+                return Err(
+                    self.value_error(&format!("can't iterate over a {}", v.type_name()), keyword)
+                )
+            }
+        };
+        Ok(ForInIter::Values(values.into_iter()))
+    }
+
+    fn next_item(
+        &mut self,
+        iter: &mut ForInIter,
+        keyword: &Token,
+    ) -> Result<Option<RloxValue>, RuntimeState> {
+        match iter {
+            ForInIter::Values(values) => Ok(values.next()),
+            ForInIter::Iterator(iterator) => {
+                let done = self.iterator_property(iterator, "done", keyword)?;
+                if self.is_truthy(&done) {
+                    return Ok(None);
+                }
+                self.iterator_property(iterator, "next", keyword).map(Some)
+            }
+        }
+    }
+
+    /// Reads `name` from an instance taking part in the iterator protocol,
+    /// calling it if it is a method.
+    fn iterator_property(
+        &mut self,
+        instance: &RloxInstance,
+        name: &str,
+        keyword: &Token,
+    ) -> EvalExprResult {
+        match instance.get(name) {
+            Some(FieldType::Field(f)) => Ok(f),
+            Some(FieldType::Method(m) | FieldType::Getter(m)) => {
+                self.call(&[], &m.bind(instance), keyword)
+            }
+            Some(FieldType::Setter(_)) | None => {
+                let text = self.stringify(&RloxValue::Instance(instance.clone()))?;
+                Err(self.value_error(
+                    &format!("{text} can't be iterated, it has no {name}"),
+                    keyword,
+                ))
+            }
+        }
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
     fn declare_fn(&mut self, stmt: &FnStmt) -> EvalStmtResult {
-        let callable = RloxValue::Callable(Callable::new(stmt.clone()));
-        self.current_env
-            .define_var(stmt.name.lexme.clone(), callable);
+        let callable = RloxValue::Callable(Callable::new(
+            stmt.clone(),
+            self.current_env.clone(),
+            self.globals.clone(),
+        ));
+        self.define(stmt.id, &stmt.name, callable);
         Ok(())
     }
 
@@ -457,27 +1050,264 @@ impl Interpreter {
         Err(RuntimeState::Cf(ControlFlow::Return(val)))
     }
 
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
     fn execute_break_stmt(&mut self, _stmt: &BreakStmt) -> EvalStmtResult {
         Err(RuntimeState::Cf(ControlFlow::Break))
     }
 
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
     fn execute_cont_stmt(&mut self, _stmt: &ContStmt) -> EvalStmtResult {
         Err(RuntimeState::Cf(ControlFlow::Continue))
     }
 
+    fn execute_throw_stmt(&mut self, stmt: &ThrowStmt) -> EvalStmtResult {
+        let value = self.evaluate(&stmt.value)?;
+        // rethrown runtime errors keep their message
+        let message = match &value {
+            RloxValue::Instance(i) if Rc::ptr_eq(&i.class(), &self.error_class) => {
+                match i.get("message") {
+                    Some(FieldType::Field(m)) => m.to_string(),
+                    _ => format!("uncaught exception: {}", self.stringify(&value)?),
+                }
+            }
+            v => format!("uncaught exception: {}", self.stringify(v)?),
+        };
+        let keyword = &stmt.keyword;
+        Err(RuntimeError::thrown_error(keyword.line, keyword.column, message, value).into())
+    }
+
+    fn execute_try_stmt(&mut self, stmt: &TryStmt) -> EvalStmtResult {
+        let result = match (self.execute_block(&stmt.body), &stmt.catch) {
+            (Err(RuntimeState::RtErr(e)), Some(catch)) => {
+                // the error is handled, a later one records its own traceback
+                self.traceback.clear();
+                let value = self.caught_value(e);
+                self.execute_catch(catch, value)
+            }
+            (result, _) => result,
+        };
+        // an error, return, break or continue from finally replaces the result
+        if let Some(finally) = &stmt.finally {
+            self.execute_block(finally)?;
+        }
+        result
+    }
+
+    fn execute_catch(&mut self, catch: &CatchClause, value: RloxValue) -> EvalStmtResult {
+        let mut new_env = Environment::new();
+        new_env.add_enclosing(&self.current_env);
+        let prev = mem::replace(&mut self.current_env, new_env);
+        self.define(catch.id, &catch.name, value);
+        let result = catch.body.iter().try_for_each(|s| self.execute(s));
+        self.current_env = prev;
+        result
+    }
+
+    /// The value a `catch` clause receives: thrown values as they are, other
+    /// runtime errors as an `Error` instance with their message and 1-based
+    /// line and column.
+    fn caught_value(&self, error: RuntimeError) -> RloxValue {
+        if let RuntimeError::Thrown(t) = error {
+            return t.value;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let fields = HashMap::from([
+            (
+                "message".to_string(),
+                RloxValue::Str(error.get_msg().to_string()),
+            ),
+            (
+                "line".to_string(),
+                RloxValue::Num((error.get_line() + 1) as f64),
+            ),
+            (
+                "column".to_string(),
+                RloxValue::Num((error.get_column() + 1) as f64),
+            ),
+        ]);
+        RloxValue::Instance(RloxInstance::new(self.error_class.clone(), fields))
+    }
+
     fn execute_class_stmt(&mut self, stmt: &Class) -> EvalStmtResult {
         let name = &stmt.name.lexme;
-        let mut methods = HashMap::new();
-        let init_params = stmt.params.iter().map(|p| p.lexme.to_string()).collect();
-        for method in &stmt.methods {
-            let callable = Callable::new(method.clone());
-            methods.insert(method.name.lexme.clone(), callable);
-        }
-        let rlox_class = RloxClass::new(name.clone(), methods, init_params);
-        self.current_env.define_class(name.clone(), rlox_class);
+        let superclass = match &stmt.superclass {
+            Some(s) => match self.variable_value(&s.name, s.id) {
+                Some(RloxValue::Class(c)) => Some(c),
+                _ => {
+                    return Err(self.value_error(
+                        &format!("superclass {} must be a class", s.name.lexme),
+                        &s.name,
+                    ))
+                }
+            },
+            None => None,
+        };
+        let mut traits = Vec::new();
+        for t in &stmt.traits {
+            match self.variable_value(&t.name, t.id) {
+                Some(RloxValue::Trait(t)) => traits.push(t),
+                _ => {
+                    return Err(
+                        self.value_error(&format!("{} is not a trait", t.name.lexme), &t.name)
+                    )
+                }
+            }
+        }
+        let mut init_params: Vec<String> = stmt.params.iter().map(|p| p.lexme.clone()).collect();
+        if let (true, Some(s)) = (init_params.is_empty(), &superclass) {
+            init_params.clone_from(&s.params);
+        }
+        let mut closure = self.current_env.clone();
+        if let Some(s) = &superclass {
+            closure = Environment::new();
+            closure.add_enclosing(&self.current_env);
+            closure.define_at(0, RloxValue::Class(s.clone()));
+        }
+        let by_name = |functions: &[FnStmt]| {
+            functions
+                .iter()
+                .map(|f| {
+                    (
+                        f.name.lexme.clone(),
+                        Callable::new(f.clone(), closure.clone(), self.globals.clone()),
+                    )
+                })
+                .collect()
+        };
+        let functions = ClassFunctions {
+            methods: by_name(&stmt.body.methods),
+            getters: by_name(&stmt.body.getters),
+            setters: by_name(&stmt.body.setters),
+            statics: by_name(&stmt.body.static_methods),
+        };
+        let rlox_class = Rc::new(RloxClass::new(
+            name.clone(),
+            superclass,
+            functions,
+            traits,
+            stmt.body.fields.clone(),
+            self.current_env.clone(),
+            init_params,
+        ));
+        for (t, token) in rlox_class.traits.iter().zip(&stmt.traits) {
+            if let Some(missing) = t
+                .required
+                .iter()
+                .find(|r| rlox_class.find_method(r).is_none())
+            {
+                return Err(self.value_error(
+                    &format!("{name} must implement {missing}() required by {}", t.name),
+                    &token.name,
+                ));
+            }
+        }
+        self.define(stmt.id, &stmt.name, RloxValue::Class(rlox_class.clone()));
+        for field in &stmt.body.static_fields {
+            let value = self.evaluate(&field.initializer)?;
+            rlox_class.define_static(field.name.lexme.clone(), value);
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn execute_trait_stmt(&mut self, stmt: &TraitStmt) -> EvalStmtResult {
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|m| {
+                (
+                    m.name.lexme.clone(),
+                    Callable::new(m.clone(), self.current_env.clone(), self.globals.clone()),
+                )
+            })
+            .collect();
+        let rlox_trait = RloxTrait {
+            name: stmt.name.lexme.clone(),
+            required: stmt.required.iter().map(|r| r.lexme.clone()).collect(),
+            methods,
+        };
+        self.define(stmt.id, &stmt.name, RloxValue::Trait(Rc::new(rlox_trait)));
+        Ok(())
+    }
+
+    fn execute_import_stmt(&mut self, stmt: &ImportStmt) -> EvalStmtResult {
+        let module = self.import(&stmt.path)?;
+        if let Some(alias) = &stmt.alias {
+            let value = RloxValue::Module(module);
+            self.current_env.define_var(alias.lexme.clone(), value);
+            return Ok(());
+        }
+        for name in &stmt.names {
+            let Some(value) = module.get(&name.lexme) else {
+                return Err(self.value_error(
+                    &format!("module \"{}\" has no export {}", module.name, name.lexme),
+                    name,
+                ));
+            };
+            self.current_env.define_var(name.lexme.clone(), value);
+        }
         Ok(())
     }
 
+    /// Returns the module of the file at `path`, relative to the importing
+    /// file, running the file the first time it is imported.
+    fn import(&mut self, path: &Token) -> Result<Rc<RloxModule>, RuntimeState> {
+        let name = path
+            .literal
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let error = |message: String| -> RuntimeState {
+            RuntimeError::value_error(path.line, path.column, message).into()
+        };
+        let file = fs::canonicalize(self.dir.join(&name))
+            .map_err(|e| error(format!("can't read module \"{name}\": {e}")))?;
+        if let Some(module) = self.modules.borrow().get(&file) {
+            return Ok(module);
+        }
+        let source = fs::read_to_string(&file)
+            .map_err(|e| error(format!("can't read module \"{name}\": {e}")))?;
+        self.modules
+            .borrow_mut()
+            .start(file.clone())
+            .map_err(error)?;
+        let module = self.run_module(&name, &file, &source).map(Rc::new);
+        self.modules
+            .borrow_mut()
+            .finish(module.as_ref().ok().cloned());
+        module.map_err(|e| {
+            let first = e.errors()[0];
+            error(format!(
+                "in \"{name}\" at line {}: {}",
+                first.get_line() + 1,
+                first.get_msg()
+            ))
+        })
+    }
+
+    /// Runs a module in globals of its own.
+    fn run_module(&self, name: &str, file: &Path, source: &str) -> Result<RloxModule, LoxError> {
+        let mut globals = Environment::new();
+        natives::define_natives(&mut globals);
+        let mut interpreter = Interpreter {
+            globals: globals.clone(),
+            current_env: globals.clone(),
+            locals: self.locals.clone(),
+            output: self.output.clone(),
+            modules: self.modules.clone(),
+            error_class: self.error_class.clone(),
+            dir: file.parent().map(Path::to_path_buf).unwrap_or_default(),
+            frames: Vec::new(),
+            traceback: Vec::new(),
+        };
+        let statements = interpreter.analyze(source, false)?;
+        let module = RloxModule::new(name.to_string(), globals, &statements);
+        interpreter
+            .interpret(&statements)
+            .map_err(|e| LoxError::Runtime(e, Vec::new()))?;
+        Ok(module)
+    }
+
     fn execute(&mut self, stmt: &Stmt) -> EvalStmtResult {
         match stmt {
             Stmt::Expresssion(e) => self.eval_expression_stmt(e),
@@ -487,23 +1317,104 @@ impl Interpreter {
             Stmt::IfStmt(i) => self.execute_if_stmt(i),
             Stmt::WhileStmt(w) => self.execute_while_stmt(w),
             Stmt::ForStmt(f) => self.execute_for_stmt(f),
+            Stmt::ForInStmt(f) => self.execute_for_in_stmt(f),
             Stmt::FnStmt(f) => self.declare_fn(f),
             Stmt::BreakStmt(f) => self.execute_break_stmt(f),
             Stmt::ContStmt(f) => self.execute_cont_stmt(f),
             Stmt::ReturnStmt(r) => self.execute_return_stmt(r),
             Stmt::Class(c) => self.execute_class_stmt(c),
+            Stmt::Trait(t) => self.execute_trait_stmt(t),
+            Stmt::Import(i) => self.execute_import_stmt(i),
+            Stmt::ThrowStmt(t) => self.execute_throw_stmt(t),
+            Stmt::TryStmt(t) => self.execute_try_stmt(t),
         }
     }
 
-    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<(), RuntimeError> {
-        for statement in &statements {
-            if let Err(e) = self.execute(statement) {
-                match e {
-                    RuntimeState::RtErr(e) => return Err(e),
-                    _ => (),
+    /// Scans, parses and resolves `source`, keeping its resolutions next to
+    /// those of the inputs and modules this interpreter already ran.
+    pub fn analyze(&mut self, source: &str, interactive: bool) -> Result<Vec<Stmt>, LoxError> {
+        let mut last_id = self.modules.borrow().last_id;
+        let analyzed = session::analyze(source, interactive, &mut last_id);
+        self.modules.borrow_mut().last_id = last_id;
+        let (statements, locals) = analyzed?;
+        self.add_locals(locals);
+        Ok(statements)
+    }
+
+    /// Makes imports relative to the directory of `path`, the file being run,
+    /// which can't be imported back.
+    pub fn set_path(&mut self, path: &Path) {
+        self.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if let Ok(file) = fs::canonicalize(path) {
+            // never finished, the file runs until the program ends
+            let _ = self.modules.borrow_mut().start(file);
+        }
+    }
+
+    /// Adds the resolutions of a newly resolved input, for sessions running
+    /// several inputs in the same interpreter.
+    fn add_locals(&mut self, locals: HashMap<usize, Local>) {
+        self.locals.borrow_mut().extend(locals);
+    }
+
+    /// Formats a value the way `print` shows it, running the `__str__`
+    /// methods of instances, also inside lists and maps.
+    pub(crate) fn stringify(&mut self, value: &RloxValue) -> Result<String, RuntimeError> {
+        match value {
+            RloxValue::Instance(i) => {
+                let Some(method) = i.bound_method("__str__") else {
+                    return Ok(i.to_string());
+                };
+                let site = &method.function.name;
+                let returned = match self.call(&[], &method, site) {
+                    Ok(v) => v,
+                    Err(RuntimeState::RtErr(e)) => return Err(e),
+                    // only a `return` ends a call with a value
+                    Err(RuntimeState::Cf(_)) => RloxValue::Nil,
+                };
+                match returned {
+                    RloxValue::Str(s) => Ok(s),
+                    v => Err(RuntimeError::value_error(
+                        site.line,
+                        site.column,
+                        format!("__str__ must return a string, found {}", v.type_name()),
+                    )),
                 }
-            };
+            }
+            RloxValue::List(l) => l.format(|v| self.stringify(v)),
+            RloxValue::Map(m) => m.format(|v| self.stringify(v)),
+            v => Ok(v.to_string()),
+        }
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            if let Err(RuntimeState::RtErr(e)) = self.execute(statement) {
+                return Err(e);
+            }
         }
         Ok(())
     }
+
+    /// Like [`Interpreter::interpret`], but returns the value of the last
+    /// statement if it is a bare expression other than an assignment, `nil`
+    /// otherwise.
+    pub fn interpret_interactive(
+        &mut self,
+        statements: &[Stmt],
+    ) -> Result<RloxValue, RuntimeError> {
+        let mut value = RloxValue::Nil;
+        for statement in statements {
+            let result = match statement {
+                Stmt::Expresssion(e) if !e.expr.is_assignment() => {
+                    self.evaluate(&e.expr).map(|v| value = v)
+                }
+                _ => self.execute(statement).map(|()| value = RloxValue::Nil),
+            };
+            if let Err(RuntimeState::RtErr(e)) = result {
+                return Err(e);
+            }
+        }
+        Ok(value)
+    }
 }
@@ -1,5 +1,5 @@
-use crate::errors::scanner_errors::*;
-use crate::token::*;
+use crate::errors::scanner_errors::{InvalidToken, ScannerError};
+use crate::token::{LiteralValue, Token, TokenType};
 
 pub struct Scanner {
     source: Vec<char>,
@@ -9,12 +9,13 @@ pub struct Scanner {
     line: usize,
     start_column: usize,
     current_column: usize,
+    /// The braces opened inside every unfinished `${`, the innermost last.
+    interpolations: Vec<usize>,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Self {
-        let mut tokens: Vec<Token> = Vec::new();
-        tokens.reserve(4096);
+    pub fn new(source: &str) -> Self {
+        let tokens: Vec<Token> = Vec::with_capacity(4096);
         Scanner {
             source: source.chars().collect(),
             tokens,
@@ -23,6 +24,7 @@ impl Scanner {
             line: 0,
             start_column: 0,
             current_column: 0,
+            interpolations: Vec::new(),
         }
     }
 
@@ -34,63 +36,84 @@ impl Scanner {
                 match c {
                     '(' => self.add_token(TokenType::LeftParen),
                     ')' => self.add_token(TokenType::RightParen),
-                    '{' => self.add_token(TokenType::LeftBrace),
-                    '}' => self.add_token(TokenType::RightBrace),
+                    '{' => {
+                        if let Some(open) = self.interpolations.last_mut() {
+                            *open += 1;
+                        }
+                        self.add_token(TokenType::LeftBrace);
+                    }
+                    '}' => match self.interpolations.last_mut() {
+                        // the end of an interpolated expression
+                        Some(0) => {
+                            self.interpolations.pop();
+                            self.process_string_literal()?;
+                        }
+                        Some(open) => {
+                            *open -= 1;
+                            self.add_token(TokenType::RightBrace);
+                        }
+                        None => self.add_token(TokenType::RightBrace),
+                    },
+                    '[' => self.add_token(TokenType::LeftBracket),
+                    ']' => self.add_token(TokenType::RightBracket),
                     ',' => self.add_token(TokenType::Comma),
+                    ':' => self.add_token(TokenType::Colon),
                     '.' => self.add_token(TokenType::Dot),
                     '+' => self.add_token(TokenType::Plus),
                     '-' => self.add_token(TokenType::Minus),
                     ';' => self.add_token(TokenType::Semicolon),
                     '*' => self.add_token(TokenType::Star),
                     '!' => {
-                        match self.char_match('=') {
-                            true => self.add_token(TokenType::BangEqual),
-                            false => self.add_token(TokenType::Bang),
-                        };
+                        if self.char_match('=') {
+                            self.add_token(TokenType::BangEqual);
+                        } else {
+                            self.add_token(TokenType::Bang);
+                        }
                     }
                     '=' => {
-                        match self.char_match('=') {
-                            true => self.add_token(TokenType::EqualEqual),
-                            false => self.add_token(TokenType::Equal),
-                        };
+                        if self.char_match('=') {
+                            self.add_token(TokenType::EqualEqual);
+                        } else if self.char_match('>') {
+                            self.add_token(TokenType::Arrow);
+                        } else {
+                            self.add_token(TokenType::Equal);
+                        }
                     }
                     '<' => {
-                        match self.char_match('=') {
-                            true => self.add_token(TokenType::LessEqual),
-                            false => self.add_token(TokenType::Less),
-                        };
+                        if self.char_match('=') {
+                            self.add_token(TokenType::LessEqual);
+                        } else {
+                            self.add_token(TokenType::Less);
+                        }
                     }
                     '>' => {
-                        match self.char_match('=') {
-                            true => self.add_token(TokenType::GreaterEqual),
-                            false => self.add_token(TokenType::Greater),
-                        };
+                        if self.char_match('=') {
+                            self.add_token(TokenType::GreaterEqual);
+                        } else {
+                            self.add_token(TokenType::Greater);
+                        }
                     }
                     '/' => {
-                        match self.char_match('/') {
-                            true => {
-                                while self.peek() != '\n' && !self.is_at_end() {
-                                    self.increment_current(1);
-                                }
-                            }
-                            false => {
-                                match self.char_match('*') {
-                                    true => self.process_block_comments()?,
-                                    false => self.add_token(TokenType::Slash),
-                                };
+                        if self.char_match('/') {
+                            while self.peek() != '\n' && !self.is_at_end() {
+                                self.increment_current(1);
                             }
-                        };
+                        } else if self.char_match('*') {
+                            self.process_block_comments()?;
+                        } else {
+                            self.add_token(TokenType::Slash);
+                        }
                     }
                     '\n' => self.increment_line(),
-                    '\t' | '\r' | ' ' => continue,
+                    '\t' | '\r' | ' ' => (),
                     '"' => self.process_string_literal()?,
                     rest => {
-                        if Scanner::char_is_num(rest) {
-                            self.process_numeric_literal()?
-                        } else if Scanner::char_is_alpha(rest) {
-                            self.process_identifier()?
+                        if Scanner::char_is_num(*rest) {
+                            self.process_numeric_literal()?;
+                        } else if Scanner::char_is_alpha(*rest) {
+                            self.process_identifier()?;
                         } else {
-                            let token = rest.clone();
+                            let token = *rest;
                             self.line += 1;
                             return Err(self.invalid_token(token));
                         }
@@ -98,6 +121,9 @@ impl Scanner {
                 }
             }
         }
+        if !self.interpolations.is_empty() {
+            return Err(self.unterminated_string());
+        }
         self.tokens.push(Token::eof_token(self.line));
         Ok(())
     }
@@ -137,74 +163,223 @@ impl Scanner {
             if *c == comp {
                 self.increment_current(1);
                 return true;
-            } else {
-                return false;
             }
+            return false;
         }
         false
     }
 
-    fn char_is_num(comp: &char) -> bool {
-        (*comp >= '0') && (*comp <= '9')
+    fn char_is_num(comp: char) -> bool {
+        comp.is_ascii_digit()
     }
 
-    fn char_is_alpha(comp: &char) -> bool {
-        (*comp >= 'a' && *comp <= 'z') || (*comp >= 'A' && *comp <= 'Z') || (*comp == '_')
+    fn char_is_alpha(comp: char) -> bool {
+        comp.is_ascii_alphabetic() || comp == '_'
     }
 
-    fn char_is_alphanum(comp: &char) -> bool {
+    fn char_is_alphanum(comp: char) -> bool {
         Scanner::char_is_num(comp) || Scanner::char_is_alpha(comp)
     }
 
+    /// Scans a string after its opening `"`, or the rest of one after an
+    /// interpolated expression. A `${` ends the token there, the scanner
+    /// comes back here at the matching `}`.
     fn process_string_literal(&mut self) -> Result<(), ScannerError> {
         // store column in case the source ends in new line
         let mut prev_column = self.current_column;
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                prev_column = self.current_column;
-                self.increment_line();
-            };
-            self.increment_current(1);
+        let mut value = String::new();
+        loop {
+            if self.is_at_end() {
+                self.current_column = prev_column;
+                return Err(self.unterminated_string());
+            }
+            match self.peek() {
+                '"' => break,
+                '\\' => value.push(self.process_escape()?),
+                '$' if self.peek_next() == '{' => {
+                    self.increment_current(2);
+                    self.interpolations.push(0);
+                    self.add_token_literal(TokenType::Interpolation, LiteralValue::Str(value));
+                    return Ok(());
+                }
+                c => {
+                    if c == '\n' {
+                        prev_column = self.current_column;
+                        self.increment_line();
+                    }
+                    value.push(c);
+                    self.increment_current(1);
+                }
+            }
         }
 
+        self.increment_current(1);
+        self.add_token_literal(TokenType::String, LiteralValue::Str(value));
+        Ok(())
+    }
+
+    /// Scans an escape sequence from its `\\` and returns the character it
+    /// stands for.
+    fn process_escape(&mut self) -> Result<char, ScannerError> {
+        let column = self.current_column;
+        self.increment_current(1);
         if self.is_at_end() {
-            self.current_column = prev_column;
             return Err(self.unterminated_string());
+        }
+        let c = match self.peek() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            'u' => return self.process_unicode_escape(column),
+            c => {
+                return Err(self.invalid_literal(column, format!("invalid escape sequence \\{c}")));
+            }
         };
+        self.increment_current(1);
+        Ok(c)
+    }
 
+    /// Scans `u{...}` after a `\\`, with 1 to 6 hex digits.
+    fn process_unicode_escape(&mut self, column: usize) -> Result<char, ScannerError> {
         self.increment_current(1);
+        if self.peek() != '{' {
+            return Err(
+                self.invalid_literal(self.current_column, "expected \"{\" after \\u".to_string())
+            );
+        }
+        self.increment_current(1);
+        let mut digits = String::new();
+        while self.peek() != '}' {
+            let c = self.peek();
+            if self.is_at_end() {
+                return Err(self.unterminated_string());
+            }
+            if !c.is_ascii_hexdigit() {
+                return Err(self.invalid_literal(
+                    self.current_column,
+                    format!("invalid character {c:?} in unicode escape"),
+                ));
+            }
+            if digits.len() == 6 {
+                return Err(self.invalid_literal(
+                    self.current_column,
+                    "a unicode escape has at most 6 hex digits".to_string(),
+                ));
+            }
+            digits.push(c);
+            self.increment_current(1);
+        }
+        self.increment_current(1);
+        if digits.is_empty() {
+            return Err(self.invalid_literal(column, "empty unicode escape".to_string()));
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| {
+                self.invalid_literal(column, format!("invalid unicode code point {digits}"))
+            })
+    }
 
-        let value: String = self.source[self.start + 1..self.current - 1]
-            .iter()
-            .collect();
-        self.add_token_literal(TokenType::String, LiteralValue::Str(value));
+    /// Scans a number from its first digit: `0x`, `0b` and `0o` integers, or
+    /// decimals with an optional fraction and exponent. `_` can separate
+    /// digits anywhere.
+    fn process_numeric_literal(&mut self) -> Result<(), ScannerError> {
+        // rescan the first digit, the separators around it are checked too
+        self.current = self.start;
+        self.current_column = self.start_column;
+        let radix = match (self.peek(), self.peek_next()) {
+            ('0', 'x' | 'X') => Some((16, "hexadecimal")),
+            ('0', 'b' | 'B') => Some((2, "binary")),
+            ('0', 'o' | 'O') => Some((8, "octal")),
+            _ => None,
+        };
+        let value = match radix {
+            Some((radix, name)) => self.radix_literal(radix, name)?,
+            None => self.decimal_literal()?,
+        };
+        self.add_token_literal(TokenType::Number, LiteralValue::Num(value));
         Ok(())
     }
 
-    fn process_numeric_literal(&mut self) -> Result<(), ScannerError> {
-        while Scanner::char_is_num(&self.peek()) {
-            self.advance();
+    fn radix_literal(&mut self, radix: u32, name: &str) -> Result<f64, ScannerError> {
+        let prefix = self.source[self.current + 1];
+        self.increment_current(2);
+        let digits = self.digits(|c| c.is_digit(radix))?;
+        if Scanner::char_is_alphanum(self.peek()) {
+            let c = self.peek();
+            let msg = format!("invalid digit {c} in {name} literal");
+            return Err(self.invalid_literal(self.current_column, msg));
         }
+        if digits.is_empty() {
+            let msg = format!("expected {name} digits after 0{prefix}");
+            return Err(self.invalid_literal(self.current_column, msg));
+        }
+        Ok(digits.chars().fold(0.0, |value, c| {
+            let digit = c.to_digit(radix).expect("expected a digit of the radix");
+            value * f64::from(radix) + f64::from(digit)
+        }))
+    }
+
+    fn decimal_literal(&mut self) -> Result<f64, ScannerError> {
+        let is_digit = |c: char| c.is_ascii_digit();
+        let mut text = self.digits(is_digit)?;
         if self.peek() == '.' {
             self.advance();
-            while Scanner::char_is_num(&self.peek()) {
-                self.advance();
+            text.push('.');
+            text.push_str(&self.digits(is_digit)?);
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            text.push('e');
+            if matches!(self.peek(), '+' | '-') {
+                text.push(*self.advance().expect("expected a sign"));
             }
+            let exponent = self.digits(is_digit)?;
+            if exponent.is_empty() {
+                return Err(self.invalid_literal(
+                    self.current_column,
+                    "expected digits in exponent".to_string(),
+                ));
+            }
+            text.push_str(&exponent);
         }
+        text.parse()
+            .map_err(|_| self.invalid_literal(self.start_column, format!("invalid number {text}")))
+    }
 
-        let value = self.source[self.start..self.current]
-            .iter()
-            .collect::<String>()
-            .parse::<f64>();
-        match value {
-            Ok(v) => self.add_token_literal(TokenType::Number, LiteralValue::Num(v)),
-            Err(_) => return Err(self.invalid_token(self.source[self.start])),
+    /// Consumes the digits `is_digit` accepts and the `_` separating them,
+    /// and returns the digits alone.
+    fn digits(&mut self, is_digit: impl Fn(char) -> bool) -> Result<String, ScannerError> {
+        let mut digits = String::new();
+        let mut after_digit = false;
+        loop {
+            let c = self.peek();
+            if is_digit(c) {
+                digits.push(c);
+                after_digit = true;
+            } else if c == '_' {
+                if !after_digit || !is_digit(self.peek_next()) {
+                    return Err(self.invalid_literal(
+                        self.current_column,
+                        "a digit separator must be between two digits".to_string(),
+                    ));
+                }
+                after_digit = false;
+            } else {
+                return Ok(digits);
+            }
+            self.increment_current(1);
         }
-        Ok(())
     }
 
+    #[allow(clippy::unnecessary_wraps)]
     fn process_identifier(&mut self) -> Result<(), InvalidToken> {
-        while Scanner::char_is_alphanum(&self.peek()) {
+        while Scanner::char_is_alphanum(self.peek()) {
             self.advance();
         }
         let value: String = self.source[self.start..self.current].iter().collect();
@@ -235,7 +410,7 @@ impl Scanner {
                         self.increment_current(2);
                     } else {
                         self.increment_current(1);
-                    };
+                    }
                 }
                 '/' => {
                     if self.peek_next() == '*' {
@@ -243,10 +418,10 @@ impl Scanner {
                         self.increment_current(2);
                     } else {
                         self.increment_current(1);
-                    };
+                    }
                 }
                 _ => self.increment_current(1),
-            };
+            }
         }
 
         if nested > 0 {
@@ -261,6 +436,11 @@ impl Scanner {
         ScannerError::invalid_token(self.line, self.current_column, token.to_string())
     }
 
+    /// An error at `column` of the current line.
+    fn invalid_literal(&self, column: usize, msg: String) -> ScannerError {
+        ScannerError::invalid_literal(self.line, column, msg)
+    }
+
     fn unterminated_string(&self) -> ScannerError {
         ScannerError::unterminated_string(self.line, self.current_column, self.get_line_text())
     }
@@ -285,7 +465,7 @@ impl Scanner {
     }
 
     fn add_token(&mut self, r#type: TokenType) {
-        let lexme = self.source[self.start..self.current].into_iter().collect();
+        let lexme = self.source[self.start..self.current].iter().collect();
         self.tokens.push(Token {
             r#type,
             lexme,
@@ -296,7 +476,7 @@ impl Scanner {
     }
 
     fn add_token_literal(&mut self, r#type: TokenType, literal: LiteralValue) {
-        let lexme = self.source[self.start..self.current].into_iter().collect();
+        let lexme = self.source[self.start..self.current].iter().collect();
         self.tokens.push(Token {
             r#type,
             lexme,
@@ -306,3 +486,60 @@ impl Scanner {
         });
     }
 }
+
+#[cfg(test)]
+fn scan_error(source: &str) -> (usize, usize, String) {
+    use crate::errors::ReportError;
+
+    let mut scanner = Scanner::new(source);
+    let error = scanner.scan_tokens().expect_err("expected a scanner error");
+    (
+        error.get_line(),
+        error.get_column(),
+        error.get_msg().to_string(),
+    )
+}
+
+#[test]
+pub fn malformed_number_columns() {
+    let cases = [
+        ("x = 0x;", 6, "expected hexadecimal digits after 0x"),
+        ("x = 0b12;", 7, "invalid digit 2 in binary literal"),
+        ("x = 1e+;", 7, "expected digits in exponent"),
+        (
+            "x = 1_000_;",
+            9,
+            "a digit separator must be between two digits",
+        ),
+        (
+            "x = 1__0;",
+            5,
+            "a digit separator must be between two digits",
+        ),
+        (
+            "x = 1._5;",
+            6,
+            "a digit separator must be between two digits",
+        ),
+    ];
+    for (source, column, msg) in cases {
+        assert_eq!(scan_error(source), (0, column, msg.to_string()), "{source}");
+    }
+}
+
+#[test]
+pub fn invalid_escape_columns() {
+    let cases = [
+        ("\n\"a\\qb\"", 2, "invalid escape sequence \\q"),
+        ("\"\\u{zz}\"", 4, "invalid character 'z' in unicode escape"),
+        ("\"\\u{D800}\"", 1, "invalid unicode code point D800"),
+    ];
+    for (source, column, msg) in cases {
+        let line = usize::from(source.starts_with('\n'));
+        assert_eq!(
+            scan_error(source),
+            (line, column, msg.to_string()),
+            "{source}"
+        );
+    }
+}
@@ -0,0 +1,24 @@
+//! A Lox interpreter. [`Session`] embeds it in a Rust program, [`run_cli`]
+//! is the command line front end.
+
+mod ast;
+mod callable;
+mod class;
+mod cli;
+mod environment;
+mod errors;
+mod interpreter;
+mod lexer;
+mod list;
+mod map;
+mod module;
+mod natives;
+mod parser;
+mod resolver;
+mod session;
+mod token;
+mod vm;
+
+pub use cli::run_cli;
+pub use errors::{LoxError, ReportError, StackFrame};
+pub use session::{Session, Value};
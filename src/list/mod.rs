@@ -0,0 +1,142 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::token::{FormatGuard, Quoted, RloxValue};
+
+/// A growable list. Clones share the same elements, like instances do.
+#[derive(Debug, Clone)]
+pub struct RloxList {
+    items: Rc<RefCell<Vec<RloxValue>>>,
+}
+
+impl RloxList {
+    pub fn new(items: Vec<RloxValue>) -> Self {
+        RloxList {
+            items: Rc::new(RefCell::new(items)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    /// A copy of the elements.
+    pub fn items(&self) -> Vec<RloxValue> {
+        self.items.borrow().clone()
+    }
+
+    /// Whether both values are the same list.
+    pub fn ptr_eq(&self, other: &RloxList) -> bool {
+        Rc::ptr_eq(&self.items, &other.items)
+    }
+
+    /// Calls `f` with every element, unless the list is shared or being
+    /// changed, for the collector to follow what a scope owns.
+    pub(crate) fn for_each_owned(&self, mut f: impl FnMut(&RloxValue)) {
+        if Rc::strong_count(&self.items) == 1 {
+            if let Ok(items) = self.items.try_borrow() {
+                items.iter().for_each(&mut f);
+            }
+        }
+    }
+
+    /// Turns a Lox number into an index below `len`.
+    #[allow(clippy::unused_self)]
+    fn index(&self, index: &RloxValue, len: usize) -> Result<usize, String> {
+        let RloxValue::Num(n) = index else {
+            return Err(format!(
+                "list index must be a number, found {}",
+                index.type_name()
+            ));
+        };
+        if n.fract() != 0.0 {
+            return Err(format!("list index must be a whole number, found {n}"));
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let i = *n as usize;
+        if *n < 0.0 || i >= len {
+            return Err(format!("list index {n} out of bounds for length {len}"));
+        }
+        Ok(i)
+    }
+
+    pub fn get(&self, index: &RloxValue) -> Result<RloxValue, String> {
+        let i = self.index(index, self.len())?;
+        Ok(self.items.borrow()[i].clone())
+    }
+
+    pub fn set(&self, index: &RloxValue, value: RloxValue) -> Result<(), String> {
+        let i = self.index(index, self.len())?;
+        self.items.borrow_mut()[i] = value;
+        Ok(())
+    }
+
+    /// Runs the built-in method `name`, `None` if there is no such method.
+    pub fn call_method(&self, name: &str, args: &[RloxValue]) -> Option<Result<RloxValue, String>> {
+        let arity = match name {
+            "push" | "remove" => 1,
+            "pop" | "len" => 0,
+            "insert" => 2,
+            _ => return None,
+        };
+        if args.len() != arity {
+            return Some(Err(format!(
+                "this function takes {arity} args but {} were given",
+                args.len()
+            )));
+        }
+        Some(match name {
+            "push" => {
+                self.items.borrow_mut().push(args[0].clone());
+                Ok(RloxValue::Nil)
+            }
+            "pop" => self
+                .items
+                .borrow_mut()
+                .pop()
+                .ok_or_else(|| "pop from empty list".to_string()),
+            #[allow(clippy::cast_precision_loss)]
+            "len" => Ok(RloxValue::Num(self.len() as f64)),
+            "insert" => {
+                // inserting right after the last element is allowed
+                self.index(&args[0], self.len() + 1).map(|i| {
+                    self.items.borrow_mut().insert(i, args[1].clone());
+                    RloxValue::Nil
+                })
+            }
+            _ => self
+                .index(&args[0], self.len())
+                .map(|i| self.items.borrow_mut().remove(i)),
+        })
+    }
+}
+
+impl RloxList {
+    /// Formats the list, with `item` formatting the elements other than
+    /// strings. The elements are copied first, as `item` may change the list.
+    pub fn format<E>(
+        &self,
+        mut item: impl FnMut(&RloxValue) -> Result<String, E>,
+    ) -> Result<String, E> {
+        let Some(_guard) = FormatGuard::enter(Rc::as_ptr(&self.items)) else {
+            return Ok("[...]".to_string());
+        };
+        let mut text = String::from("[");
+        for (i, element) in self.items().iter().enumerate() {
+            if i > 0 {
+                text.push_str(", ");
+            }
+            match element {
+                RloxValue::Str(_) => text.push_str(&Quoted(element).to_string()),
+                v => text.push_str(&item(v)?),
+            }
+        }
+        text.push(']');
+        Ok(text)
+    }
+}
+
+impl fmt::Display for RloxList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(|v| Ok(v.to_string()))?)
+    }
+}
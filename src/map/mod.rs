@@ -0,0 +1,196 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    list::RloxList,
+    token::{FormatGuard, Quoted, RloxValue},
+};
+
+/// The values that can be map keys. Numbers compare by value, with `-0`
+/// equal to `0`, and `NaN` is rejected since it is not equal to itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MapKey {
+    Str(String),
+    Num(u64),
+    Bool(bool),
+    Nil,
+}
+
+impl MapKey {
+    fn new(value: &RloxValue) -> Result<Self, String> {
+        match value {
+            RloxValue::Str(s) => Ok(MapKey::Str(s.clone())),
+            RloxValue::Num(n) if n.is_nan() => Err("NaN can't be a map key".to_string()),
+            RloxValue::Num(n) => Ok(MapKey::Num(if *n == 0.0 { 0 } else { n.to_bits() })),
+            RloxValue::Bool(b) => Ok(MapKey::Bool(*b)),
+            RloxValue::Nil => Ok(MapKey::Nil),
+            v => Err(format!(
+                "map keys must be strings, numbers, booleans or nil, found {}",
+                v.type_name()
+            )),
+        }
+    }
+
+    fn value(&self) -> RloxValue {
+        match self {
+            MapKey::Str(s) => RloxValue::Str(s.clone()),
+            MapKey::Num(bits) => RloxValue::Num(f64::from_bits(*bits)),
+            MapKey::Bool(b) => RloxValue::Bool(*b),
+            MapKey::Nil => RloxValue::Nil,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MapCtx {
+    /// Entries in insertion order.
+    entries: Vec<(MapKey, RloxValue)>,
+    /// Position of every key in `entries`.
+    indices: HashMap<MapKey, usize>,
+}
+
+/// A hash map that keeps its keys in insertion order. Clones share the same
+/// entries, like instances do.
+#[derive(Debug, Clone)]
+pub struct RloxMap {
+    ctx: Rc<RefCell<MapCtx>>,
+}
+
+impl RloxMap {
+    pub fn new(entries: Vec<(RloxValue, RloxValue)>) -> Result<Self, String> {
+        let map = RloxMap {
+            ctx: Rc::new(RefCell::new(MapCtx::default())),
+        };
+        for (key, value) in entries {
+            map.set(&key, value)?;
+        }
+        Ok(map)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ctx.borrow().entries.len()
+    }
+
+    /// Whether both values are the same map.
+    pub fn ptr_eq(&self, other: &RloxMap) -> bool {
+        Rc::ptr_eq(&self.ctx, &other.ctx)
+    }
+
+    /// Calls `f` with every value, unless the map is shared or being changed,
+    /// for the collector to follow what a scope owns. Keys can't hold scopes.
+    pub(crate) fn for_each_owned(&self, mut f: impl FnMut(&RloxValue)) {
+        if Rc::strong_count(&self.ctx) == 1 {
+            if let Ok(ctx) = self.ctx.try_borrow() {
+                ctx.entries.iter().for_each(|(_, value)| f(value));
+            }
+        }
+    }
+
+    pub fn get(&self, key: &RloxValue) -> Result<RloxValue, String> {
+        let ctx = self.ctx.borrow();
+        match ctx.indices.get(&MapKey::new(key)?) {
+            Some(&i) => Ok(ctx.entries[i].1.clone()),
+            None => Err(format!("undefined map key {}", Quoted(key))),
+        }
+    }
+
+    pub fn set(&self, key: &RloxValue, value: RloxValue) -> Result<(), String> {
+        let key = MapKey::new(key)?;
+        let mut ctx = self.ctx.borrow_mut();
+        if let Some(&i) = ctx.indices.get(&key) {
+            ctx.entries[i].1 = value;
+        } else {
+            let i = ctx.entries.len();
+            ctx.indices.insert(key.clone(), i);
+            ctx.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn has(&self, key: &RloxValue) -> Result<bool, String> {
+        Ok(self.ctx.borrow().indices.contains_key(&MapKey::new(key)?))
+    }
+
+    /// Removes `key` and returns its value, `nil` if it was not there.
+    fn remove(&self, key: &RloxValue) -> Result<RloxValue, String> {
+        let key = MapKey::new(key)?;
+        let mut ctx = self.ctx.borrow_mut();
+        let Some(i) = ctx.indices.remove(&key) else {
+            return Ok(RloxValue::Nil);
+        };
+        let (_, value) = ctx.entries.remove(i);
+        for index in ctx.indices.values_mut() {
+            if *index > i {
+                *index -= 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// The keys in insertion order.
+    pub fn keys(&self) -> Vec<RloxValue> {
+        let ctx = self.ctx.borrow();
+        ctx.entries.iter().map(|(k, _)| k.value()).collect()
+    }
+
+    fn values(&self) -> Vec<RloxValue> {
+        let ctx = self.ctx.borrow();
+        ctx.entries.iter().map(|(_, v)| v.clone()).collect()
+    }
+
+    /// Runs the built-in method `name`, `None` if there is no such method.
+    pub fn call_method(&self, name: &str, args: &[RloxValue]) -> Option<Result<RloxValue, String>> {
+        let arity = match name {
+            "has" | "remove" => 1,
+            "keys" | "values" | "len" => 0,
+            _ => return None,
+        };
+        if args.len() != arity {
+            return Some(Err(format!(
+                "this function takes {arity} args but {} were given",
+                args.len()
+            )));
+        }
+        Some(match name {
+            "has" => self.has(&args[0]).map(RloxValue::Bool),
+            "remove" => self.remove(&args[0]),
+            "keys" => Ok(RloxValue::List(RloxList::new(self.keys()))),
+            "values" => Ok(RloxValue::List(RloxList::new(self.values()))),
+            #[allow(clippy::cast_precision_loss)]
+            _ => Ok(RloxValue::Num(self.len() as f64)),
+        })
+    }
+}
+
+impl RloxMap {
+    /// Formats the map, with `item` formatting the values other than strings.
+    /// The entries are copied first, as `item` may change the map.
+    pub fn format<E>(
+        &self,
+        mut item: impl FnMut(&RloxValue) -> Result<String, E>,
+    ) -> Result<String, E> {
+        let Some(_guard) = FormatGuard::enter(Rc::as_ptr(&self.ctx)) else {
+            return Ok("{...}".to_string());
+        };
+        let entries = self.ctx.borrow().entries.clone();
+        let mut text = String::from("{");
+        for (i, (key, value)) in entries.iter().enumerate() {
+            if i > 0 {
+                text.push_str(", ");
+            }
+            text.push_str(&Quoted(&key.value()).to_string());
+            text.push_str(": ");
+            match value {
+                RloxValue::Str(_) => text.push_str(&Quoted(value).to_string()),
+                v => text.push_str(&item(v)?),
+            }
+        }
+        text.push('}');
+        Ok(text)
+    }
+}
+
+impl fmt::Display for RloxMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(|v| Ok(v.to_string()))?)
+    }
+}
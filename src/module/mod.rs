@@ -0,0 +1,107 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{ast::stmt::Stmt, environment::Environment, token::RloxValue};
+
+/// A file run by an `import`. Its top-level bindings are its exports.
+#[derive(Debug)]
+pub struct RloxModule {
+    /// The path as it was written in the first import of the module.
+    pub name: String,
+    globals: Environment,
+    exports: Vec<String>,
+}
+
+impl RloxModule {
+    /// The module whose statements were run with `globals` as their globals.
+    pub fn new(name: String, globals: Environment, statements: &[Stmt]) -> Self {
+        let exports = statements.iter().flat_map(declared_names).collect();
+        RloxModule {
+            name,
+            globals,
+            exports,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<RloxValue> {
+        if !self.exports.iter().any(|e| e == name) {
+            return None;
+        }
+        self.globals.get_var(name)
+    }
+}
+
+/// The names a top-level statement binds.
+fn declared_names(stmt: &Stmt) -> Vec<String> {
+    match stmt {
+        Stmt::Var(v) => vec![v.name.lexme.clone()],
+        Stmt::FnStmt(f) => vec![f.name.lexme.clone()],
+        Stmt::Class(c) => vec![c.name.lexme.clone()],
+        Stmt::Trait(t) => vec![t.name.lexme.clone()],
+        Stmt::Import(i) => i
+            .alias
+            .iter()
+            .chain(&i.names)
+            .map(|t| t.lexme.clone())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl fmt::Display for RloxModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+/// The modules of a program, shared by the interpreters running its files.
+#[derive(Default)]
+pub struct Modules {
+    /// Modules that finished running, by canonical path.
+    loaded: HashMap<PathBuf, Rc<RloxModule>>,
+    /// The files being run, the outermost first.
+    loading: Vec<PathBuf>,
+    /// The nodes of all files share one id space, as their resolutions are
+    /// kept in one map.
+    pub last_id: usize,
+}
+
+impl Modules {
+    pub fn get(&self, path: &Path) -> Option<Rc<RloxModule>> {
+        self.loaded.get(path).cloned()
+    }
+
+    /// Marks `path` as being run, or returns the chain of imports that leads
+    /// back to it, as file names.
+    pub fn start(&mut self, path: PathBuf) -> Result<(), String> {
+        if let Some(i) = self.loading.iter().position(|p| *p == path) {
+            let cycle: Vec<String> = self.loading[i..]
+                .iter()
+                .chain([&path])
+                .map(|p| file_name(p))
+                .collect();
+            return Err(format!("import cycle: {}", cycle.join(" -> ")));
+        }
+        self.loading.push(path);
+        Ok(())
+    }
+
+    /// Stops running the innermost file, caching the module it defined.
+    pub fn finish(&mut self, module: Option<Rc<RloxModule>>) {
+        let path = self.loading.pop().expect("expected a file being run");
+        if let Some(module) = module {
+            self.loaded.insert(path, module);
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |n| n.to_string_lossy().to_string(),
+    )
+}
@@ -0,0 +1,205 @@
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{callable::NativeFunction, environment::Environment, token::RloxValue};
+
+/// What the built-in functions need to know about a value. Both backends
+/// implement it for their own values, so every native is written once.
+pub trait NativeValue: Clone {
+    fn nil() -> Self;
+    fn from_bool(b: bool) -> Self;
+    fn from_num(n: f64) -> Self;
+    fn from_string(s: String) -> Self;
+    fn as_num(&self) -> Option<f64>;
+    fn as_str(&self) -> Option<&str>;
+    /// The number of characters, elements or entries of a string, a list or
+    /// a map.
+    fn len(&self) -> Option<usize>;
+    fn type_name(&self) -> &'static str;
+    /// Whether `self` was composed with `t`, `None` if `t` is not a trait.
+    fn implements(&self, t: &Self) -> Option<bool>;
+}
+
+/// A built-in function. An `Err` is reported as a runtime error at the call
+/// site.
+#[derive(Debug)]
+pub struct Native<V> {
+    pub name: &'static str,
+    pub arity: usize,
+    /// Whether the backend formats the arguments into strings first, the way
+    /// `print` does.
+    pub formats_args: bool,
+    pub function: fn(&[V]) -> Result<V, String>,
+}
+
+impl<V> Native<V> {
+    fn new(name: &'static str, arity: usize, function: fn(&[V]) -> Result<V, String>) -> Self {
+        Native {
+            name,
+            arity,
+            formats_args: false,
+            function,
+        }
+    }
+}
+
+/// The built-in functions of both backends.
+pub fn natives<V: NativeValue>() -> [Native<V>; 7] {
+    [
+        Native::new("clock", 0, clock),
+        Native::new("input", 0, input),
+        Native::new("len", 1, len),
+        Native {
+            formats_args: true,
+            ..Native::new("str", 1, str)
+        },
+        Native::new("num", 1, num),
+        Native::new("type", 1, type_name),
+        Native::new("implements", 2, implements),
+    ]
+}
+
+/// Defines the built-in functions as globals of `env`.
+pub fn define_natives(env: &mut Environment) {
+    for native in natives::<RloxValue>() {
+        let mut function = NativeFunction::new(native.name, native.arity, native.function);
+        if native.formats_args {
+            function = function.formatting_args();
+        }
+        env.define_var(native.name.to_string(), RloxValue::NativeFunction(function));
+    }
+}
+
+impl NativeValue for RloxValue {
+    fn nil() -> Self {
+        RloxValue::Nil
+    }
+
+    fn from_bool(b: bool) -> Self {
+        RloxValue::Bool(b)
+    }
+
+    fn from_num(n: f64) -> Self {
+        RloxValue::Num(n)
+    }
+
+    fn from_string(s: String) -> Self {
+        RloxValue::Str(s)
+    }
+
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            RloxValue::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            RloxValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> Option<usize> {
+        match self {
+            RloxValue::Str(s) => Some(s.chars().count()),
+            RloxValue::List(l) => Some(l.len()),
+            RloxValue::Map(m) => Some(m.len()),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        RloxValue::type_name(self)
+    }
+
+    fn implements(&self, t: &Self) -> Option<bool> {
+        let RloxValue::Trait(t) = t else {
+            return None;
+        };
+        let class = match self {
+            RloxValue::Instance(i) => i.class(),
+            RloxValue::Class(c) => c.clone(),
+            _ => return Some(false),
+        };
+        Some(class.implements(t))
+    }
+}
+
+/// Seconds since the unix epoch.
+fn clock<V: NativeValue>(_: &[V]) -> Result<V, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?;
+    Ok(V::from_num(now.as_secs_f64()))
+}
+
+/// Reads a line from stdin without its line ending, or `nil` at end of input.
+fn input<V: NativeValue>(_: &[V]) -> Result<V, String> {
+    let mut line = String::new();
+    let read = io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    if read == 0 {
+        return Ok(V::nil());
+    }
+    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed);
+    Ok(V::from_string(line))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn len<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    match args[0].len() {
+        Some(len) => Ok(V::from_num(len as f64)),
+        None => Err(format!(
+            "len() expects a string, a list or a map, found {}",
+            args[0].type_name()
+        )),
+    }
+}
+
+/// The argument, which the backend already formatted.
+#[allow(clippy::unnecessary_wraps)]
+fn str<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    Ok(args[0].clone())
+}
+
+/// Parses a string into a number, `nil` if it is not a finite one.
+fn num<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    if let Some(n) = args[0].as_num() {
+        return Ok(V::from_num(n));
+    }
+    match args[0].as_str() {
+        Some(s) => Ok(s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .map_or(V::nil(), V::from_num)),
+        None => Err(format!(
+            "num() expects a string, found {}",
+            args[0].type_name()
+        )),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn type_name<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    Ok(V::from_string(args[0].type_name().to_string()))
+}
+
+/// Whether an instance or a class was composed with a trait, directly or
+/// through a superclass.
+fn implements<V: NativeValue>(args: &[V]) -> Result<V, String> {
+    match args[0].implements(&args[1]) {
+        Some(implements) => Ok(V::from_bool(implements)),
+        None => Err(format!(
+            "implements() expects a trait, found {}",
+            args[1].type_name()
+        )),
+    }
+}
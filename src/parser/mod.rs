@@ -1,9 +1,11 @@
-use std::{iter::Peekable, vec::IntoIter};
+use std::vec::IntoIter;
 
 use crate::{
     ast::{
-        expr::Expr,
-        stmt::{BreakStmt, ContStmt, FnStmt, ForStmtInitializer, Stmt},
+        expr::{Expr, Variable},
+        stmt::{
+            BreakStmt, CatchClause, ClassBody, ContStmt, FnStmt, ForStmtInitializer, Stmt, VarStmt,
+        },
     },
     errors::parser_errors::ParserError,
     token::{LiteralValue, Token, TokenType},
@@ -12,44 +14,80 @@ use crate::{
 type ParseExprResult = Result<Expr, ParserError>;
 type ParseStmtResult = Result<Stmt, ParserError>;
 
-pub struct Parser  {
-    tokens: Peekable<IntoIter<Token>>,
+pub struct Parser {
+    tokens: IntoIter<Token>,
     current: usize,
+    last_id: usize,
+    session: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        let tokens = tokens.into_iter().peekable();
-        Parser { tokens, current: 0  }
+        let tokens = tokens.into_iter();
+        Parser {
+            tokens,
+            current: 0,
+            last_id: 0,
+            session: false,
+        }
+    }
+
+    /// Creates a parser whose node ids continue after `last_id`, so they
+    /// don't clash with the ids of inputs parsed before.
+    pub fn continuing(tokens: Vec<Token>, last_id: usize) -> Self {
+        Parser {
+            last_id,
+            ..Parser::new(tokens)
+        }
+    }
+
+    /// Like [`Parser::continuing`], for one input of an interactive session
+    /// where a trailing expression may leave out its `;`.
+    pub fn for_session(tokens: Vec<Token>, last_id: usize) -> Self {
+        Parser {
+            session: true,
+            ..Parser::continuing(tokens, last_id)
+        }
+    }
+
+    pub fn last_id(&self) -> usize {
+        self.last_id
     }
 
     fn peek(&mut self) -> &Token {
-        self.tokens.peek().expect("unexpected eof")
+        self.tokens.as_slice().first().expect("unexpected eof")
         // &self.tokens[self.current]
     }
 
     // fn previous(&self) -> &'a Token {
-        // self.previous.expect("unexpected eof")
-        // // &self.tokens[self.current - 1]
+    // self.previous.expect("unexpected eof")
+    // // &self.tokens[self.current - 1]
     // }
 
     fn is_at_end(&mut self) -> bool {
-        match self.peek().r#type {
-            TokenType::Eof => true,
-            _ => false,
-        }
+        self.peek().r#type == TokenType::Eof
     }
 
     fn advance(&mut self) -> Token {
+        // never move past eof, error recovery may try to
+        if self.is_at_end() {
+            return self.peek().clone();
+        }
         let token = self.tokens.next().expect("unexpected eof");
         self.current += 1;
-        return token
+        token
         // if !self.is_at_end() {
-            // self.current += 1;
+        // self.current += 1;
         // }
         // self.previous()
     }
 
+    /// Returns a fresh id for a node the resolver needs to annotate.
+    fn next_id(&mut self) -> usize {
+        self.last_id += 1;
+        self.last_id
+    }
+
     fn synchronize(&mut self) {
         let token = self.advance();
         while !self.is_at_end() {
@@ -58,6 +96,9 @@ impl Parser {
                 _ => {
                     match self.peek().r#type {
                         TokenType::Class
+                        | TokenType::Trait
+                        | TokenType::Import
+                        | TokenType::From
                         | TokenType::Fun
                         | TokenType::Var
                         | TokenType::For
@@ -68,7 +109,7 @@ impl Parser {
                         _ => self.advance(),
                     };
                 }
-            };
+            }
         }
     }
 
@@ -89,19 +130,11 @@ impl Parser {
     }
 
     fn expr_error(&mut self, msg: &str) -> ParserError {
-        ParserError::invalid_expression(
-            self.advance().line,
-            self.advance().column,
-            msg.to_string(),
-        )
+        ParserError::invalid_expression(self.advance().line, self.advance().column, msg.to_string())
     }
 
     fn stmt_error(&mut self, msg: &str) -> ParserError {
-        ParserError::invalid_stmt(
-            self.advance().line,
-            self.advance().column,
-            msg.to_string(),
-        )
+        ParserError::invalid_stmt(self.advance().line, self.advance().column, msg.to_string())
     }
 
     fn primary(&mut self) -> ParseExprResult {
@@ -114,6 +147,19 @@ impl Parser {
                 Some(v) => Ok(Expr::literal(v.clone())),
                 None => Err(self.missing_literal()),
             },
+            TokenType::Interpolation => self.interpolation(token),
+            TokenType::LeftParen if self.at_arrow_params() => {
+                let params = self.fn_params()?;
+                let arrow = self.advance();
+                let body = if self.peek().r#type == TokenType::LeftBrace {
+                    self.advance();
+                    self.block()?
+                } else {
+                    let value = self.expression()?;
+                    vec![Stmt::return_stmt(arrow.clone(), Some(value))]
+                };
+                Ok(self.lambda(&arrow, params, body))
+            }
             TokenType::LeftParen => {
                 let expr = self.expression()?;
                 match self.peek().r#type {
@@ -124,47 +170,179 @@ impl Parser {
                     _ => Err(self.missing_paren()),
                 }
             }
-            TokenType::This => Ok(Expr::this(token)),
-            TokenType::Identifier => Ok(Expr::variable(token, self.current)),
+            TokenType::This => Ok(Expr::this(token, self.next_id())),
+            TokenType::Super => {
+                if self.advance().r#type != TokenType::Dot {
+                    return Err(self.expr_error("expected \".\" after \"super\""));
+                }
+                if self.peek().r#type != TokenType::Identifier {
+                    return Err(self.expr_error("expected superclass method name"));
+                }
+                let method = self.advance();
+                let method_args = self.method_args()?;
+                Ok(Expr::super_expr(token, method, method_args, self.next_id()))
+            }
+            TokenType::Identifier => Ok(Expr::variable(token, self.next_id())),
+            TokenType::Fun => {
+                if self.advance().r#type != TokenType::LeftParen {
+                    return Err(self.expr_error("expected \"(\" after \"fun\""));
+                }
+                let params = self.fn_params()?;
+                if self.peek().r#type != TokenType::LeftBrace {
+                    return Err(self.expr_error("expected \"{\" before function body"));
+                }
+                self.advance();
+                let body = self.block()?;
+                Ok(self.lambda(&token, params, body))
+            }
+            TokenType::LeftBracket => {
+                let elements = self.list_elements()?;
+                Ok(Expr::list(token, elements))
+            }
+            // a brace starts a block where a statement is expected
+            TokenType::LeftBrace => {
+                let entries = self.map_entries()?;
+                Ok(Expr::map(token, entries))
+            }
             _ => Err(self.expr_error(format!("Invalid token {}", token.lexme).as_str())),
         }
     }
 
+    /// Parses a string with interpolated expressions from the part before
+    /// the first one. Every expression is followed by the next part, the
+    /// last by a string token.
+    fn interpolation(&mut self, quote: Token) -> ParseExprResult {
+        let mut parts = Vec::new();
+        let mut part = quote.clone();
+        loop {
+            if let Some(LiteralValue::Str(s)) = &part.literal {
+                if !s.is_empty() {
+                    parts.push(Expr::literal(LiteralValue::Str(s.clone())));
+                }
+            }
+            if part.r#type == TokenType::String {
+                return Ok(Expr::interpolation(quote, parts));
+            }
+            parts.push(self.expression()?);
+            match self.peek().r#type {
+                TokenType::Interpolation | TokenType::String => part = self.advance(),
+                _ => return Err(self.expr_error("expected \"}\" after interpolated expression")),
+            }
+        }
+    }
+
+    /// Whether the tokens after a `(` are the parameters of an arrow function,
+    /// like `(a, b) =>`.
+    fn at_arrow_params(&self) -> bool {
+        let mut tokens = self.tokens.as_slice().iter();
+        let mut expect_param = true;
+        while let Some(token) = tokens.next() {
+            match (&token.r#type, expect_param) {
+                (TokenType::RightParen, _) => {
+                    return tokens.next().is_some_and(|t| t.r#type == TokenType::Arrow)
+                }
+                (TokenType::Identifier, true) => expect_param = false,
+                (TokenType::Comma, false) => expect_param = true,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Builds an anonymous function, `token` gives its position.
+    fn lambda(&mut self, token: &Token, params: Vec<Token>, body: Vec<Stmt>) -> Expr {
+        let name = Token {
+            lexme: "<lambda>".to_string(),
+            ..token.clone()
+        };
+        Expr::lambda(FnStmt {
+            name,
+            params,
+            body,
+            id: self.next_id(),
+        })
+    }
+
+    /// Parses the elements of a list literal after its `[`, up to and
+    /// including the `]`. A trailing comma is allowed.
+    fn list_elements(&mut self) -> Result<Vec<Expr>, ParserError> {
+        let mut elements = Vec::new();
+        while self.peek().r#type != TokenType::RightBracket {
+            elements.push(self.expression()?);
+            if self.peek().r#type == TokenType::Comma {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        if self.peek().r#type != TokenType::RightBracket {
+            return Err(self.expr_error("missing \"]\" at the end of list"));
+        }
+        self.advance();
+        Ok(elements)
+    }
+
+    /// Parses the `key: value` pairs of a map literal after its `{`, up to and
+    /// including the `}`. A trailing comma is allowed.
+    fn map_entries(&mut self) -> Result<Vec<(Expr, Expr)>, ParserError> {
+        let mut entries = Vec::new();
+        while self.peek().r#type != TokenType::RightBrace {
+            let key = self.expression()?;
+            if self.peek().r#type != TokenType::Colon {
+                return Err(self.expr_error("missing \":\" after map key"));
+            }
+            self.advance();
+            entries.push((key, self.expression()?));
+            if self.peek().r#type == TokenType::Comma {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        if self.peek().r#type != TokenType::RightBrace {
+            return Err(self.expr_error("missing \"}\" at the end of map"));
+        }
+        self.advance();
+        Ok(entries)
+    }
+
+    fn method_args(&mut self) -> Result<Option<Vec<Expr>>, ParserError> {
+        if self.peek().r#type != TokenType::LeftParen {
+            return Ok(None);
+        }
+        self.advance();
+        let mut args = Vec::new();
+        loop {
+            if self.peek().r#type == TokenType::RightParen {
+                break;
+            }
+            if args.len() > 255 {
+                return Err(self.stmt_error("Can't have more than 255 arguments"));
+            }
+            args.push(self.expression()?);
+            if self.peek().r#type == TokenType::Comma {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.advance();
+        Ok(Some(args))
+    }
+
     fn call(&mut self) -> ParseExprResult {
-        let prev = self.peek().lexme.clone();
         let mut expr = self.primary()?;
         loop {
             match self.peek().r#type {
                 TokenType::Dot => {
                     self.advance();
                     let name = self.advance();
-                    let mut method_args = None;
-                    if self.peek().r#type == TokenType::LeftParen {
-                        self.advance();
-                        let mut args = Vec::new();
-                        loop {
-                            if self.peek().r#type == TokenType::RightParen {
-                                break;
-                            }
-                            if args.len() > 255 {
-                                return Err(self.stmt_error("Can't have more than 255 arguments"));
-                            }
-                            args.push(self.expression()?);
-                            if self.peek().r#type == TokenType::Comma {
-                                self.advance();
-                            } else {
-                                break;
-                            }
-                        }
-                        self.advance();
-                        method_args = Some(args);
-                    }
+                    let method_args = self.method_args()?;
                     expr = Expr::get(name, expr, method_args);
                 }
                 TokenType::LeftParen => {
                     self.advance();
-                    let mut args = Vec::new();
-                    args.reserve(255);
+                    let mut args = Vec::with_capacity(255);
                     loop {
                         if self.peek().r#type == TokenType::RightParen {
                             break;
@@ -183,7 +361,16 @@ impl Parser {
                         return Err(self.stmt_error("missing \")\" for function call"));
                     }
                     let paren = self.advance();
-                    expr = Expr::call(prev.clone(), paren, args);
+                    expr = Expr::call(expr, paren, args);
+                }
+                TokenType::LeftBracket => {
+                    let bracket = self.advance();
+                    let index = self.expression()?;
+                    if self.peek().r#type != TokenType::RightBracket {
+                        return Err(self.expr_error("missing \"]\" after index"));
+                    }
+                    self.advance();
+                    expr = Expr::index(expr, bracket, index);
                 }
                 _ => break,
             }
@@ -204,95 +391,68 @@ impl Parser {
 
     fn factor(&mut self) -> ParseExprResult {
         let mut expr = self.unary()?;
-        loop {
-            match self.peek().r#type {
-                TokenType::Slash | TokenType::Star => {
-                    let operator = self.advance();
-                    let right = self.unary();
-                    expr = Expr::binary(expr, operator, right?);
-                }
-                _ => break,
-            }
+        while matches!(self.peek().r#type, TokenType::Slash | TokenType::Star) {
+            let operator = self.advance();
+            let right = self.unary();
+            expr = Expr::binary(expr, operator, right?);
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn term(&mut self) -> ParseExprResult {
         let mut expr = self.factor()?;
-        loop {
-            match self.peek().r#type {
-                TokenType::Plus | TokenType::Minus => {
-                    let operator = self.advance();
-                    let right = self.factor()?;
-                    expr = Expr::binary(expr, operator, right);
-                }
-                _ => break,
-            }
+        while matches!(self.peek().r#type, TokenType::Plus | TokenType::Minus) {
+            let operator = self.advance();
+            let right = self.factor()?;
+            expr = Expr::binary(expr, operator, right);
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn comparison(&mut self) -> ParseExprResult {
         let mut expr = self.term()?;
-        loop {
-            match self.peek().r#type {
-                TokenType::Greater
-                | TokenType::GreaterEqual
-                | TokenType::Less
-                | TokenType::LessEqual => {
-                    let operator = self.advance();
-                    let right = self.term()?;
-                    expr = Expr::binary(expr, operator, right);
-                }
-                _ => break,
-            }
+        while matches!(
+            self.peek().r#type,
+            TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual
+        ) {
+            let operator = self.advance();
+            let right = self.term()?;
+            expr = Expr::binary(expr, operator, right);
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn equality(&mut self) -> ParseExprResult {
         let mut expr = self.comparison()?;
-        loop {
-            match self.peek().r#type {
-                TokenType::BangEqual | TokenType::EqualEqual => {
-                    let operator = self.advance();
-                    let right = self.comparison()?;
-                    expr = Expr::binary(expr, operator, right);
-                }
-                _ => break,
-            }
+        while matches!(
+            self.peek().r#type,
+            TokenType::BangEqual | TokenType::EqualEqual
+        ) {
+            let operator = self.advance();
+            let right = self.comparison()?;
+            expr = Expr::binary(expr, operator, right);
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn and(&mut self) -> ParseExprResult {
         let mut expr = self.equality()?;
-        loop {
-            match self.peek().r#type {
-                TokenType::And => {
-                    let operator = self.advance();
-                    let right = self.comparison();
-                    expr = Expr::logical(expr, operator, right?);
-                }
-                _ => break,
-            }
+        while matches!(self.peek().r#type, TokenType::And) {
+            let operator = self.advance();
+            let right = self.comparison();
+            expr = Expr::logical(expr, operator, right?);
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn or(&mut self) -> ParseExprResult {
         let mut expr = self.and()?;
-        loop {
-            match self.peek().r#type {
-                TokenType::Or => {
-                    let operator = self.advance();
-                    let right = self.comparison();
-                    expr = Expr::logical(expr, operator, right?);
-                }
-                _ => break,
-            }
+        while matches!(self.peek().r#type, TokenType::Or) {
+            let operator = self.advance();
+            let right = self.comparison();
+            expr = Expr::logical(expr, operator, right?);
         }
-        return Ok(expr);
+        Ok(expr)
     }
 
     fn assignment(&mut self) -> ParseExprResult {
@@ -301,12 +461,17 @@ impl Parser {
             TokenType::Equal => {
                 self.advance();
                 match expr {
-                    Expr::Variable(v) => Ok(Expr::assign(
-                        v.name,
+                    Expr::Variable(v) => {
+                        let value = self.assignment()?;
+                        Ok(Expr::assign(v.name, value, self.next_id()))
+                    }
+                    Expr::Get(g) => Ok(Expr::set(g.name, *g.object, self.assignment()?)),
+                    Expr::Index(i) => Ok(Expr::set_index(
+                        *i.object,
+                        i.bracket,
+                        *i.key,
                         self.assignment()?,
-                        self.current,
                     )),
-                    Expr::Get(g) => Ok(Expr::set(g.name, *g.object, self.assignment()?)),
                     _ => Err(self.expr_error("invalid var assignment")),
                 }
             }
@@ -328,6 +493,9 @@ impl Parser {
 
     fn expression_statement(&mut self) -> ParseStmtResult {
         let expr = self.expression()?;
+        if self.session && self.is_at_end() {
+            return Ok(Stmt::expression(expr));
+        }
         match self.advance().r#type {
             TokenType::Semicolon => Ok(Stmt::expression(expr)),
             _ => Err(self.missing_semicolon()),
@@ -350,10 +518,10 @@ impl Parser {
             }
         }
 
-        if !right_brace {
-            Err(self.expr_error("expected \"}\" after block"))
-        } else {
+        if right_brace {
             Ok(statements)
+        } else {
+            Err(self.expr_error("expected \"}\" after block"))
         }
     }
 
@@ -366,9 +534,7 @@ impl Parser {
         let else_branch = match self.peek().r#type {
             TokenType::Else => {
                 self.advance();
-                let ret = Some(self.statement()?);
-                self.advance();
-                ret
+                Some(self.statement()?)
             }
             _ => None,
         };
@@ -401,7 +567,14 @@ impl Parser {
         let initializer = match self.peek().r#type {
             TokenType::Var => {
                 self.advance();
-                match self.var_declaration()? {
+                if self.peek().r#type != TokenType::Identifier {
+                    return Err(self.stmt_error("expected a variable name"));
+                }
+                let name = self.advance();
+                if self.peek().r#type == TokenType::In {
+                    return self.for_in_statement(name);
+                }
+                match self.var_initializer(name)? {
                     Stmt::Var(v) => Some(ForStmtInitializer::VarDecl(v)),
                     _ => return Err(self.stmt_error("invalid for loop initialization")),
                 }
@@ -423,7 +596,7 @@ impl Parser {
 
         if self.advance().r#type != TokenType::Semicolon {
             return Err(self.stmt_error("missing \";\" after loop condition"));
-        };
+        }
 
         let afterthought = match self.peek().r#type {
             TokenType::RightParen => None,
@@ -432,13 +605,30 @@ impl Parser {
 
         if self.advance().r#type != TokenType::RightParen {
             return Err(self.stmt_error("missing \")\" after loop construct"));
-        };
+        }
 
         let body = self.statement()?;
 
         Ok(Stmt::for_stmt(body, initializer, condition, afterthought))
     }
 
+    /// Parses the rest of `for (var name in iterable) body` from the `in`.
+    fn for_in_statement(&mut self, name: Token) -> ParseStmtResult {
+        let keyword = self.advance();
+        let iterable = self.expression()?;
+        if self.advance().r#type != TokenType::RightParen {
+            return Err(self.stmt_error("missing \")\" after loop construct"));
+        }
+        let body = self.statement()?;
+        Ok(Stmt::for_in_stmt(
+            name,
+            keyword,
+            iterable,
+            body,
+            self.next_id(),
+        ))
+    }
+
     fn create_fn_statment(&mut self) -> Result<FnStmt, ParserError> {
         if self.peek().r#type != TokenType::Identifier {
             return Err(self.stmt_error("expected function name"));
@@ -448,12 +638,29 @@ impl Parser {
             return Err(self.stmt_error("expected \"(\" after function name"));
         }
         self.advance();
+        let params = self.fn_params()?;
+        if self.peek().r#type != TokenType::LeftBrace {
+            return Err(self.stmt_error("expected \"{\" before function body"));
+        }
+        self.advance();
+        let body = self.block()?;
+        Ok(FnStmt {
+            name,
+            params,
+            body,
+            id: self.next_id(),
+        })
+    }
+
+    /// Parses the parameter names after a function's `(`, up to and including
+    /// the `)`.
+    fn fn_params(&mut self) -> Result<Vec<Token>, ParserError> {
         let mut params = Vec::new();
         loop {
             let token = self.advance();
             match token.r#type {
                 TokenType::RightParen => break,
-                TokenType::Comma => continue,
+                TokenType::Comma => (),
                 TokenType::Identifier => {
                     if params.len() < 256 {
                         params.push(token);
@@ -464,12 +671,7 @@ impl Parser {
                 _ => return Err(self.stmt_error("invalid function param")),
             }
         }
-        if self.peek().r#type != TokenType::LeftBrace {
-            return Err(self.stmt_error("expected \"{\" before function body"));
-        }
-        self.advance();
-        let body = self.block()?;
-        Ok(FnStmt { name, params, body })
+        Ok(params)
     }
 
     fn fn_statement(&mut self) -> ParseStmtResult {
@@ -488,6 +690,105 @@ impl Parser {
         Ok(Stmt::return_stmt(token, value))
     }
 
+    fn throw_statement(&mut self, token: Token) -> ParseStmtResult {
+        let value = self.expression()?;
+        if self.peek().r#type != TokenType::Semicolon {
+            return Err(self.missing_semicolon());
+        }
+        self.advance();
+        Ok(Stmt::throw_stmt(token, value))
+    }
+
+    fn module_path(&mut self, after: &str) -> Result<Token, ParserError> {
+        if self.peek().r#type != TokenType::String {
+            return Err(self.stmt_error(&format!("expected a module path after \"{after}\"")));
+        }
+        Ok(self.advance())
+    }
+
+    fn import_statement(&mut self, token: Token) -> ParseStmtResult {
+        let path = self.module_path("import")?;
+        if self.peek().r#type != TokenType::As {
+            return Err(self.stmt_error("expected \"as\" after the module path"));
+        }
+        self.advance();
+        if self.peek().r#type != TokenType::Identifier {
+            return Err(self.stmt_error("expected a module name after \"as\""));
+        }
+        let alias = self.advance();
+        if self.peek().r#type != TokenType::Semicolon {
+            return Err(self.missing_semicolon());
+        }
+        self.advance();
+        Ok(Stmt::import_stmt(token, path, Some(alias), Vec::new()))
+    }
+
+    fn import_names_statement(&mut self, token: Token) -> ParseStmtResult {
+        let path = self.module_path("from")?;
+        if self.peek().r#type != TokenType::Import {
+            return Err(self.stmt_error("expected \"import\" after the module path"));
+        }
+        self.advance();
+        let mut names = Vec::new();
+        loop {
+            if self.peek().r#type != TokenType::Identifier {
+                return Err(self.stmt_error("expected a name to import"));
+            }
+            names.push(self.advance());
+            if self.peek().r#type != TokenType::Comma {
+                break;
+            }
+            self.advance();
+        }
+        if self.peek().r#type != TokenType::Semicolon {
+            return Err(self.missing_semicolon());
+        }
+        self.advance();
+        Ok(Stmt::import_stmt(token, path, None, names))
+    }
+
+    /// Parses the `{` opening the block that must follow `after`.
+    fn block_start(&mut self, after: &str) -> Result<(), ParserError> {
+        if self.peek().r#type != TokenType::LeftBrace {
+            return Err(self.stmt_error(&format!("expected \"{{\" after \"{after}\"")));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn try_statement(&mut self, token: Token) -> ParseStmtResult {
+        self.block_start("try")?;
+        let body = self.block()?;
+        let mut catch = None;
+        if self.peek().r#type == TokenType::Catch {
+            self.advance();
+            if self.advance().r#type != TokenType::LeftParen {
+                return Err(self.stmt_error("missing \"(\" after \"catch\""));
+            }
+            if self.peek().r#type != TokenType::Identifier {
+                return Err(self.stmt_error("expected a variable name"));
+            }
+            let name = self.advance();
+            if self.advance().r#type != TokenType::RightParen {
+                return Err(self.stmt_error("missing \")\" after catch variable"));
+            }
+            self.block_start("catch")?;
+            let body = self.block()?;
+            let id = self.next_id();
+            catch = Some(CatchClause { name, body, id });
+        }
+        let mut finally = None;
+        if self.peek().r#type == TokenType::Finally {
+            self.advance();
+            self.block_start("finally")?;
+            finally = Some(self.block()?);
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(self.stmt_error("expected \"catch\" or \"finally\" after try block"));
+        }
+        Ok(Stmt::try_stmt(token, body, catch, finally))
+    }
+
     fn break_statement(&mut self) -> ParseStmtResult {
         if self.peek().r#type != TokenType::Semicolon {
             return Err(self.missing_semicolon());
@@ -507,22 +808,18 @@ impl Parser {
     fn class_statement(&mut self) -> ParseStmtResult {
         let name = self.advance();
         let mut args = Vec::new();
-        let mut methods = Vec::new();
+        let mut body = ClassBody::default();
         if name.r#type != TokenType::Identifier {
             return Err(self.stmt_error("missing class name"));
         }
-        match self.advance().r#type {
-            TokenType::LeftParen => loop {
+        let id = self.next_id();
+        if self.peek().r#type == TokenType::LeftParen {
+            self.advance();
+            loop {
                 let token = self.advance();
                 match token.r#type {
-                    TokenType::RightParen => {
-                        if self.peek().r#type == TokenType::Semicolon {
-                            self.advance();
-                            return Ok(Stmt::class_stmt(name, methods, args));
-                        };
-                        break;
-                    }
-                    TokenType::Comma => continue,
+                    TokenType::RightParen => break,
+                    TokenType::Comma => (),
                     TokenType::Identifier => {
                         if args.len() < 256 {
                             args.push(token);
@@ -532,11 +829,26 @@ impl Parser {
                     }
                     _ => return Err(self.stmt_error("invalid function param")),
                 }
-            },
-            TokenType::Semicolon => {
-                return Ok(Stmt::class_stmt(name, methods, args));
             }
-            _ => (),
+        }
+
+        let mut superclass = None;
+        if self.peek().r#type == TokenType::Less {
+            self.advance();
+            if self.peek().r#type != TokenType::Identifier {
+                return Err(self.stmt_error("expected superclass name after '<'"));
+            }
+            superclass = Some(Variable {
+                name: self.advance(),
+                id: self.next_id(),
+            });
+        }
+
+        let traits = self.trait_list()?;
+
+        if self.peek().r#type == TokenType::Semicolon {
+            self.advance();
+            return Ok(Stmt::class_stmt(name, superclass, traits, body, args, id));
         }
 
         if self.advance().r#type != TokenType::LeftBrace {
@@ -544,12 +856,179 @@ impl Parser {
         }
 
         loop {
-            match self.advance().r#type {
-                TokenType::Fun => methods.push(self.create_fn_statment()?),
-                TokenType::Var => (),
-                TokenType::RightBrace => return Ok(Stmt::class_stmt(name, methods, args)),
-                _ => (),
+            let is_static = self.peek().r#type == TokenType::Static;
+            if is_static {
+                self.advance();
             }
+            match (self.peek().r#type.clone(), is_static) {
+                (TokenType::Fun, false) => {
+                    self.advance();
+                    body.methods.push(self.create_fn_statment()?);
+                }
+                (TokenType::Fun, true) => {
+                    self.advance();
+                    body.static_methods.push(self.create_fn_statment()?);
+                }
+                (TokenType::Identifier, false) if self.at_accessor() => {
+                    if self.advance().lexme == "get" {
+                        body.getters.push(self.getter()?);
+                    } else {
+                        body.setters.push(self.setter()?);
+                    }
+                }
+                (TokenType::Var, false) => body.fields.push(self.field_declaration()?),
+                (TokenType::Var, true) => body.static_fields.push(self.field_declaration()?),
+                (TokenType::RightBrace, false) => {
+                    self.advance();
+                    break;
+                }
+                (TokenType::Eof, _) => return Err(self.stmt_error("missing '}' after class body")),
+                _ => {
+                    let token = self.advance();
+                    let expected = if is_static {
+                        "expected a method or a field after \"static\""
+                    } else {
+                        "expected a method or a field in class body"
+                    };
+                    return Err(ParserError::invalid_stmt(
+                        token.line,
+                        token.column,
+                        format!("{expected}, found {}", token.lexme),
+                    ));
+                }
+            }
+        }
+        let has_init = body.methods.iter().any(|m| m.name.lexme == "init");
+        if has_init && !args.is_empty() {
+            return Err(ParserError::invalid_stmt(
+                name.line,
+                name.column,
+                "a class with an init method can't have parameters".to_string(),
+            ));
+        }
+        Ok(Stmt::class_stmt(name, superclass, traits, body, args, id))
+    }
+
+    /// The traits after `with` in a class declaration, empty without one.
+    fn trait_list(&mut self) -> Result<Vec<Variable>, ParserError> {
+        let mut traits = Vec::new();
+        if self.peek().r#type != TokenType::With {
+            return Ok(traits);
+        }
+        self.advance();
+        loop {
+            if self.peek().r#type != TokenType::Identifier {
+                return Err(self.stmt_error("expected trait name after 'with'"));
+            }
+            traits.push(Variable {
+                name: self.advance(),
+                id: self.next_id(),
+            });
+            if self.peek().r#type != TokenType::Comma {
+                return Ok(traits);
+            }
+            self.advance();
+        }
+    }
+
+    fn trait_statement(&mut self) -> ParseStmtResult {
+        let name = self.advance();
+        if name.r#type != TokenType::Identifier {
+            return Err(self.stmt_error("missing trait name"));
+        }
+        let id = self.next_id();
+        if self.advance().r#type != TokenType::LeftBrace {
+            return Err(self.stmt_error("missing '{' before trait body"));
+        }
+        let mut required = Vec::new();
+        let mut methods = Vec::new();
+        loop {
+            match self.peek().r#type {
+                TokenType::Fun => {
+                    self.advance();
+                    if self.peek().r#type != TokenType::Identifier {
+                        return Err(self.stmt_error("expected function name"));
+                    }
+                    let name = self.advance();
+                    if self.advance().r#type != TokenType::LeftParen {
+                        return Err(self.stmt_error("expected \"(\" after function name"));
+                    }
+                    let params = self.fn_params()?;
+                    match self.advance().r#type {
+                        TokenType::Semicolon => required.push(name),
+                        TokenType::LeftBrace => methods.push(FnStmt {
+                            name,
+                            params,
+                            body: self.block()?,
+                            id: self.next_id(),
+                        }),
+                        _ => {
+                            return Err(
+                                self.stmt_error("expected \";\" or a body after a trait method")
+                            )
+                        }
+                    }
+                }
+                TokenType::RightBrace => {
+                    self.advance();
+                    break;
+                }
+                TokenType::Eof => return Err(self.stmt_error("missing '}' after trait body")),
+                _ => {
+                    let token = self.advance();
+                    return Err(ParserError::invalid_stmt(
+                        token.line,
+                        token.column,
+                        format!("expected a method in trait body, found {}", token.lexme),
+                    ));
+                }
+            }
+        }
+        Ok(Stmt::trait_stmt(name, required, methods, id))
+    }
+
+    /// Whether the class body continues with `get name` or `set name`.
+    fn at_accessor(&self) -> bool {
+        let tokens = self.tokens.as_slice();
+        matches!(tokens.first(), Some(t) if t.lexme == "get" || t.lexme == "set")
+            && matches!(tokens.get(1), Some(t) if t.r#type == TokenType::Identifier)
+    }
+
+    /// Parses `name { body }` after `get`.
+    fn getter(&mut self) -> Result<FnStmt, ParserError> {
+        let name = self.advance();
+        if self.peek().r#type != TokenType::LeftBrace {
+            return Err(self.stmt_error("expected \"{\" after getter name"));
+        }
+        self.advance();
+        let body = self.block()?;
+        Ok(FnStmt {
+            name,
+            params: Vec::new(),
+            body,
+            id: self.next_id(),
+        })
+    }
+
+    /// Parses `name(value) { body }` after `set`.
+    fn setter(&mut self) -> Result<FnStmt, ParserError> {
+        let setter = self.create_fn_statment()?;
+        if setter.params.len() != 1 {
+            return Err(ParserError::invalid_stmt(
+                setter.name.line,
+                setter.name.column,
+                "a setter must take exactly one parameter".to_string(),
+            ));
+        }
+        Ok(setter)
+    }
+
+    /// Parses a `var` declaration in a class body.
+    fn field_declaration(&mut self) -> Result<VarStmt, ParserError> {
+        self.advance();
+        match self.var_declaration()? {
+            Stmt::Var(v) => Ok(v),
+            _ => Err(self.stmt_error("invalid field declaration")),
         }
     }
 
@@ -595,6 +1074,26 @@ impl Parser {
                 self.advance();
                 Ok(self.class_statement()?)
             }
+            TokenType::Trait => {
+                self.advance();
+                Ok(self.trait_statement()?)
+            }
+            TokenType::Throw => {
+                let token = self.advance();
+                Ok(self.throw_statement(token)?)
+            }
+            TokenType::Import => {
+                let token = self.advance();
+                Ok(self.import_statement(token)?)
+            }
+            TokenType::From => {
+                let token = self.advance();
+                Ok(self.import_names_statement(token)?)
+            }
+            TokenType::Try => {
+                let token = self.advance();
+                Ok(self.try_statement(token)?)
+            }
             _ => self.expression_statement(),
         }
     }
@@ -603,22 +1102,27 @@ impl Parser {
         match self.peek().r#type {
             TokenType::Identifier => {
                 let name = self.advance();
-                let initializer = match self.peek().r#type {
-                    TokenType::Equal => {
-                        self.advance();
-                        Some(self.expression()?)
-                    }
-                    _ => None,
-                };
-                match self.advance().r#type {
-                    TokenType::Semicolon => Ok(Stmt::var(name, initializer)),
-                    _ => Err(self.missing_semicolon()),
-                }
+                self.var_initializer(name)
             }
             _ => Err(self.stmt_error("expected a variable name")),
         }
     }
 
+    /// Parses the rest of a variable declaration after its name.
+    fn var_initializer(&mut self, name: Token) -> ParseStmtResult {
+        let initializer = match self.peek().r#type {
+            TokenType::Equal => {
+                self.advance();
+                Some(self.expression()?)
+            }
+            _ => None,
+        };
+        match self.advance().r#type {
+            TokenType::Semicolon => Ok(Stmt::var(name, initializer, self.next_id())),
+            _ => Err(self.missing_semicolon()),
+        }
+    }
+
     fn declaration(&mut self) -> ParseStmtResult {
         let stmt_result = match self.peek().r#type {
             TokenType::Var => {
@@ -628,11 +1132,11 @@ impl Parser {
             _ => self.statement(),
         };
 
-        if let Err(_) = stmt_result {
+        if stmt_result.is_err() {
             self.synchronize();
         }
 
-        return stmt_result;
+        stmt_result
     }
 
     pub fn parse(&mut self) -> (Vec<Stmt>, Vec<ParserError>) {
@@ -1,14 +1,24 @@
 use std::collections::HashMap;
 
+#[allow(clippy::wildcard_imports)]
 use crate::{
     ast::{expr::*, stmt::*},
     errors::resolver_errors::ResolverError,
     token::Token,
 };
 
+#[derive(Clone, Copy)]
 enum ResolveValue<'a> {
     Assign(&'a Assign),
     Var(&'a Variable),
+    This(&'a This),
+    Super(&'a Super),
+    VarDecl(&'a VarStmt),
+    FnDecl(&'a FnStmt),
+    ClassDecl(&'a Class),
+    TraitDecl(&'a TraitStmt),
+    ForIn(&'a ForInStmt),
+    Catch(&'a CatchClause),
 }
 
 impl ResolveValue<'_> {
@@ -16,21 +26,64 @@ impl ResolveValue<'_> {
         match &self {
             Self::Var(v) => v.id,
             Self::Assign(a) => a.id,
+            Self::This(t) => t.id,
+            Self::Super(s) => s.id,
+            Self::VarDecl(v) => v.id,
+            Self::FnDecl(f) => f.id,
+            Self::ClassDecl(c) => c.id,
+            Self::TraitDecl(t) => t.id,
+            Self::ForIn(f) => f.id,
+            Self::Catch(c) => c.id,
         }
     }
 }
 
+/// Where a local variable lives: how many scopes up from the current one
+/// (`depth`) and its index in that scope (`slot`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Local {
+    pub depth: usize,
+    pub slot: usize,
+}
+
+struct ScopeVar {
+    defined: bool,
+    slot: usize,
+}
+
 #[derive(Clone, Copy, PartialEq)]
 enum FunctionType {
     None,
     Function,
     Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+    Trait,
+}
+
+/// The methods a trait declares, to check the classes composed with it.
+struct TraitMethods {
+    required: Vec<String>,
+    defaults: Vec<String>,
 }
 
 pub struct Resolver {
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<String, ScopeVar>>,
     current_function: FunctionType,
-    pub resolved_locals: HashMap<usize, usize>,
+    current_class: ClassType,
+    /// Traits by name, the check for required methods is skipped when a
+    /// trait is not known here.
+    traits: HashMap<String, TraitMethods>,
+    /// The methods of every class by name, inherited ones included, `None`
+    /// when its superclass is not known.
+    class_methods: HashMap<String, Option<Vec<String>>>,
+    pub resolved_locals: HashMap<usize, Local>,
 }
 
 type ResolveResult = Result<(), ResolverError>;
@@ -41,6 +94,9 @@ impl Resolver {
             scopes: Vec::new(),
             resolved_locals: HashMap::new(),
             current_function: FunctionType::None,
+            current_class: ClassType::None,
+            traits: HashMap::new(),
+            class_methods: HashMap::new(),
         }
     }
 
@@ -49,6 +105,10 @@ impl Resolver {
         ResolverError::resolve_var_error(token.line, token.column, msg)
     }
 
+    fn class_error(token: &Token, msg: &str) -> ResolverError {
+        ResolverError::resolve_class_error(token.line, token.column, msg.to_string())
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
     }
@@ -57,33 +117,35 @@ impl Resolver {
         self.scopes.pop();
     }
 
-    fn declare(&mut self, name: &Token) {
-        if self.scopes.is_empty() {
-            return;
-        };
-        let scope = self.scopes.last_mut();
-        match scope {
-            Some(s) => s.insert(name.lexme.clone(), false),
-            None => todo!("error"),
-        };
+    /// Adds `name` to the innermost scope, giving it the next free slot unless
+    /// it was already declared there.
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let slot = scope.len();
+            scope
+                .entry(name.to_string())
+                .or_insert(ScopeVar {
+                    defined: false,
+                    slot,
+                })
+                .defined = false;
+        }
     }
 
-    fn define(&mut self, name: &Token) {
-        if self.scopes.is_empty() {
-            return;
-        };
-        let scope = self.scopes.last_mut();
-        match scope {
-            Some(s) => s.insert(name.lexme.clone(), true),
-            None => todo!("error"),
-        };
+    fn define(&mut self, name: &str) {
+        if let Some(var) = self.scopes.last_mut().and_then(|s| s.get_mut(name)) {
+            var.defined = true;
+        }
     }
 
-    fn resolve_local(&mut self, value: ResolveValue, name: &Token) {
+    fn resolve_local(&mut self, value: ResolveValue, name: &str) {
         for (i, scope) in self.scopes.iter().enumerate().rev() {
-            if scope.contains_key(&name.lexme) {
-                self.resolved_locals
-                    .insert(value.get_id(), self.scopes.len() - i - 1);
+            if let Some(var) = scope.get(name) {
+                let local = Local {
+                    depth: self.scopes.len() - i - 1,
+                    slot: var.slot,
+                };
+                self.resolved_locals.insert(value.get_id(), local);
                 return;
             }
         }
@@ -99,21 +161,30 @@ impl Resolver {
     }
 
     fn resolve_var_stmt(&mut self, stmt: &VarStmt) -> ResolveResult {
-        self.declare(&stmt.name);
+        self.declare(&stmt.name.lexme);
         self.resolve_expr(&stmt.initializer)?;
-        self.define(&stmt.name);
+        self.define(&stmt.name.lexme);
+        self.resolve_local(ResolveValue::VarDecl(stmt), &stmt.name.lexme);
         Ok(())
     }
 
+    fn resolve_fun_decl(&mut self, stmt: &FnStmt) -> ResolveResult {
+        self.declare(&stmt.name.lexme);
+        self.define(&stmt.name.lexme);
+        self.resolve_local(ResolveValue::FnDecl(stmt), &stmt.name.lexme);
+        self.resolve_fun_stmt(stmt, FunctionType::Function)
+    }
+
     fn resolve_fun_stmt(&mut self, stmt: &FnStmt, fn_type: FunctionType) -> ResolveResult {
-        self.declare(&stmt.name);
-        self.define(&stmt.name);
         let enclosing_function = self.current_function;
         self.current_function = fn_type;
         self.begin_scope();
         for param in &stmt.params {
-            self.declare(param);
-            self.define(param);
+            self.declare(&param.lexme);
+            self.define(&param.lexme);
+        }
+        for s in &stmt.body {
+            self.resolve_stmt(s)?;
         }
         self.end_scope();
         self.current_function = enclosing_function;
@@ -127,9 +198,7 @@ impl Resolver {
 
     fn resolve_if_stmt(&mut self, stmt: &IfStmt) -> ResolveResult {
         self.resolve_expr(&stmt.condition)?;
-        if let Some(t) = &stmt.else_branch {
-            self.resolve_stmt(t.as_ref())?;
-        }
+        self.resolve_stmt(stmt.then_branch.as_ref())?;
         if let Some(s) = &stmt.else_branch {
             self.resolve_stmt(s.as_ref())?;
         }
@@ -140,15 +209,32 @@ impl Resolver {
         if self.current_function == FunctionType::None {
             return Err(Resolver::var_error(
                 &stmt.keyword,
-                "can't read local variables in its own initializer",
+                "can't return from top-level code",
             ));
         }
         if let Some(v) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                return Err(Resolver::var_error(
+                    &stmt.keyword,
+                    "can't return a value from an initializer",
+                ));
+            }
             self.resolve_expr(v)?;
         }
         Ok(())
     }
 
+    /// Imported names are globals of the importing file.
+    fn resolve_import_stmt(&mut self, stmt: &ImportStmt) -> ResolveResult {
+        if !self.scopes.is_empty() {
+            return Err(Resolver::var_error(
+                &stmt.keyword,
+                "imports are only allowed at the top level",
+            ));
+        }
+        Ok(())
+    }
+
     fn resolve_while_stmt(&mut self, stmt: &WhileStmt) -> ResolveResult {
         self.resolve_expr(&stmt.condition)?;
         self.resolve_stmt(stmt.body.as_ref())?;
@@ -156,30 +242,163 @@ impl Resolver {
     }
 
     fn resolve_for_stmt(&mut self, stmt: &ForStmt) -> ResolveResult {
+        self.begin_scope();
         if let Some(i) = &stmt.initializer {
             match i {
-                ForStmtInitializer::VarDecl(v) => self.resolve_var_stmt(&v)?,
-                ForStmtInitializer::ExprStmt(v) => self.resolve_expr_stmt(&v)?,
+                ForStmtInitializer::VarDecl(v) => self.resolve_var_stmt(v)?,
+                ForStmtInitializer::ExprStmt(v) => self.resolve_expr_stmt(v)?,
             }
         }
         if let Some(c) = &stmt.condition {
             self.resolve_expr(c)?;
         }
+        if let Some(a) = &stmt.afterthought {
+            self.resolve_expr(a)?;
+        }
+        self.resolve_stmt(stmt.body.as_ref())?;
+        self.end_scope();
+        Ok(())
+    }
+
+    /// The loop variable lives in a scope of its own, which the interpreter
+    /// creates anew for every iteration.
+    fn resolve_for_in_stmt(&mut self, stmt: &ForInStmt) -> ResolveResult {
+        self.resolve_expr(&stmt.iterable)?;
+        self.begin_scope();
+        self.declare(&stmt.name.lexme);
+        self.define(&stmt.name.lexme);
+        self.resolve_local(ResolveValue::ForIn(stmt), &stmt.name.lexme);
         self.resolve_stmt(stmt.body.as_ref())?;
+        self.end_scope();
+        Ok(())
+    }
+
+    fn resolve_try_stmt(&mut self, stmt: &TryStmt) -> ResolveResult {
+        self.resolve_block_stmt(&stmt.body)?;
+        if let Some(catch) = &stmt.catch {
+            self.begin_scope();
+            self.declare(&catch.name.lexme);
+            self.define(&catch.name.lexme);
+            self.resolve_local(ResolveValue::Catch(catch), &catch.name.lexme);
+            for s in &catch.body {
+                self.resolve_stmt(s)?;
+            }
+            self.end_scope();
+        }
+        if let Some(finally) = &stmt.finally {
+            self.resolve_block_stmt(finally)?;
+        }
         Ok(())
     }
 
     fn resolve_class_stmt(&mut self, stmt: &Class) -> ResolveResult {
-        self.declare(&stmt.name);
-        self.define(&stmt.name);
+        self.declare(&stmt.name.lexme);
+        self.define(&stmt.name.lexme);
+        self.resolve_local(ResolveValue::ClassDecl(stmt), &stmt.name.lexme);
+        // defaults are evaluated where the class is declared, without `this`
+        for field in stmt.body.fields.iter().chain(&stmt.body.static_fields) {
+            self.resolve_expr(&field.initializer)?;
+        }
+        for t in &stmt.traits {
+            self.resolve_variable_expr(t)?;
+        }
+        self.check_required_methods(stmt)?;
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.lexme == stmt.name.lexme {
+                return Err(Resolver::class_error(
+                    &superclass.name,
+                    "a class can't inherit from itself",
+                ));
+            }
+            self.resolve_variable_expr(superclass)?;
+            self.current_class = ClassType::Subclass;
+            self.begin_scope();
+            self.declare("super");
+            self.define("super");
+        }
         self.begin_scope();
-        if let Some(h) = self.scopes.last_mut() {
-            h.insert("this".to_string(), true);
+        self.declare("this");
+        self.define("this");
+        for method in &stmt.body.methods {
+            let fn_type = if method.name.lexme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_fun_stmt(method, fn_type)?;
+        }
+        let body = &stmt.body;
+        let accessors = body.getters.iter().chain(&body.setters);
+        for method in accessors.chain(&body.static_methods) {
+            self.resolve_fun_stmt(method, FunctionType::Method)?;
+        }
+        self.end_scope();
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+        Ok(())
+    }
+
+    /// Checks that the class implements the required methods of its traits,
+    /// itself, through a superclass, or with the default of another trait.
+    fn check_required_methods(&mut self, stmt: &Class) -> ResolveResult {
+        let inherited = match &stmt.superclass {
+            Some(s) => self.class_methods.get(&s.name.lexme).cloned().flatten(),
+            None => Some(Vec::new()),
+        };
+        let Some(mut methods) = inherited else {
+            self.class_methods.insert(stmt.name.lexme.clone(), None);
+            return Ok(());
+        };
+        methods.extend(stmt.body.methods.iter().map(|m| m.name.lexme.clone()));
+        for t in &stmt.traits {
+            if let Some(methods_of) = self.traits.get(&t.name.lexme) {
+                methods.extend(methods_of.defaults.iter().cloned());
+            }
+        }
+        for t in &stmt.traits {
+            let Some(methods_of) = self.traits.get(&t.name.lexme) else {
+                continue;
+            };
+            if let Some(missing) = methods_of.required.iter().find(|r| !methods.contains(r)) {
+                return Err(Resolver::class_error(
+                    &t.name,
+                    &format!(
+                        "{} must implement {missing}() required by {}",
+                        stmt.name.lexme, t.name.lexme
+                    ),
+                ));
+            }
         }
+        self.class_methods
+            .insert(stmt.name.lexme.clone(), Some(methods));
+        Ok(())
+    }
+
+    fn resolve_trait_stmt(&mut self, stmt: &TraitStmt) -> ResolveResult {
+        self.declare(&stmt.name.lexme);
+        self.define(&stmt.name.lexme);
+        self.resolve_local(ResolveValue::TraitDecl(stmt), &stmt.name.lexme);
+        self.traits.insert(
+            stmt.name.lexme.clone(),
+            TraitMethods {
+                required: stmt.required.iter().map(|r| r.lexme.clone()).collect(),
+                defaults: stmt.methods.iter().map(|m| m.name.lexme.clone()).collect(),
+            },
+        );
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Trait;
+        self.begin_scope();
+        self.declare("this");
+        self.define("this");
         for method in &stmt.methods {
             self.resolve_fun_stmt(method, FunctionType::Method)?;
         }
         self.end_scope();
+        self.current_class = enclosing_class;
         Ok(())
     }
 
@@ -187,21 +406,21 @@ impl Resolver {
         let is_init = self
             .scopes
             .last()
-            .map(|s| s.get(&expr.name.lexme).unwrap_or(&true))
-            .unwrap_or(&false);
-        if !self.scopes.is_empty() && !is_init {
+            .and_then(|s| s.get(&expr.name.lexme))
+            .is_none_or(|v| v.defined);
+        if !is_init {
             return Err(Resolver::var_error(
                 &expr.name,
                 "can't read local variables in its own initializer",
             ));
         }
-        self.resolve_local(ResolveValue::Var(expr), &expr.name);
+        self.resolve_local(ResolveValue::Var(expr), &expr.name.lexme);
         Ok(())
     }
 
     fn resolve_assign_expr(&mut self, expr: &Assign) -> ResolveResult {
         self.resolve_expr(expr.value.as_ref())?;
-        self.resolve_local(ResolveValue::Assign(expr), &expr.name);
+        self.resolve_local(ResolveValue::Assign(expr), &expr.name.lexme);
         Ok(())
     }
 
@@ -212,6 +431,7 @@ impl Resolver {
     }
 
     fn resolve_call_expr(&mut self, expr: &Call) -> ResolveResult {
+        self.resolve_expr(&expr.callee)?;
         for arg in &expr.args {
             self.resolve_expr(arg)?;
         }
@@ -236,6 +456,11 @@ impl Resolver {
 
     fn resolve_get_expr(&mut self, expr: &Get) -> ResolveResult {
         self.resolve_expr(&expr.object)?;
+        if let Some(args) = &expr.method_args {
+            for arg in args {
+                self.resolve_expr(arg)?;
+            }
+        }
         Ok(())
     }
 
@@ -246,9 +471,42 @@ impl Resolver {
     }
 
     fn resolve_this(&mut self, expr: &This) -> ResolveResult {
+        if self.current_class == ClassType::None {
+            return Err(Resolver::class_error(
+                &expr.keyword,
+                "can't use 'this' outside of a class",
+            ));
+        }
+        self.resolve_local(ResolveValue::This(expr), "this");
         Ok(())
     }
 
+    fn resolve_super(&mut self, expr: &Super) -> ResolveResult {
+        match self.current_class {
+            ClassType::None => Err(Resolver::class_error(
+                &expr.keyword,
+                "can't use 'super' outside of a class",
+            )),
+            ClassType::Class => Err(Resolver::class_error(
+                &expr.keyword,
+                "can't use 'super' in a class with no superclass",
+            )),
+            ClassType::Trait => Err(Resolver::class_error(
+                &expr.keyword,
+                "can't use 'super' in a trait",
+            )),
+            ClassType::Subclass => {
+                self.resolve_local(ResolveValue::Super(expr), "super");
+                if let Some(args) = &expr.method_args {
+                    for arg in args {
+                        self.resolve_expr(arg)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
     fn resolve_expr(&mut self, expr: &Expr) -> ResolveResult {
         match expr {
             Expr::Assign(a) => self.resolve_assign_expr(a),
@@ -261,6 +519,36 @@ impl Resolver {
             Expr::Get(g) => self.resolve_get_expr(g),
             Expr::Set(s) => self.resolve_set_expr(s),
             Expr::This(t) => self.resolve_this(t),
+            Expr::Super(s) => self.resolve_super(s),
+            Expr::List(l) => {
+                for element in &l.elements {
+                    self.resolve_expr(element)?;
+                }
+                Ok(())
+            }
+            Expr::Interpolation(i) => {
+                for part in &i.parts {
+                    self.resolve_expr(part)?;
+                }
+                Ok(())
+            }
+            Expr::Lambda(l) => self.resolve_fun_stmt(&l.function, FunctionType::Function),
+            Expr::Map(m) => {
+                for (key, value) in &m.entries {
+                    self.resolve_expr(key)?;
+                    self.resolve_expr(value)?;
+                }
+                Ok(())
+            }
+            Expr::Index(i) => {
+                self.resolve_expr(&i.object)?;
+                self.resolve_expr(&i.key)
+            }
+            Expr::SetIndex(s) => {
+                self.resolve_expr(&s.object)?;
+                self.resolve_expr(&s.key)?;
+                self.resolve_expr(&s.value)
+            }
             Expr::Literal(_) => Ok(()),
         }
     }
@@ -271,12 +559,17 @@ impl Resolver {
             Stmt::Print(p) => self.resolve_expr_stmt(p),
             Stmt::Expresssion(e) => self.resolve_expr_stmt(e),
             Stmt::Block(b) => self.resolve_block_stmt(b),
-            Stmt::FnStmt(f) => self.resolve_fun_stmt(f, self.current_function),
+            Stmt::FnStmt(f) => self.resolve_fun_decl(f),
             Stmt::IfStmt(i) => self.resolve_if_stmt(i),
             Stmt::ForStmt(f) => self.resolve_for_stmt(f),
+            Stmt::ForInStmt(f) => self.resolve_for_in_stmt(f),
             Stmt::WhileStmt(w) => self.resolve_while_stmt(w),
             Stmt::ReturnStmt(r) => self.resolve_return_stmt(r),
             Stmt::Class(c) => self.resolve_class_stmt(c),
+            Stmt::Trait(t) => self.resolve_trait_stmt(t),
+            Stmt::Import(i) => self.resolve_import_stmt(i),
+            Stmt::ThrowStmt(t) => self.resolve_expr(&t.value),
+            Stmt::TryStmt(t) => self.resolve_try_stmt(t),
             _ => Ok(()),
         }
     }
@@ -0,0 +1,144 @@
+mod value;
+
+use std::{collections::HashMap, io::Write, path::Path};
+
+use crate::{
+    ast::stmt::Stmt,
+    callable::NativeFunction,
+    environment::Environment,
+    errors::LoxError,
+    interpreter::Interpreter,
+    lexer::scanner::Scanner,
+    parser::Parser,
+    resolver::{Local, Resolver},
+    token::RloxValue,
+};
+
+pub use self::value::Value;
+
+/// Scans, parses and resolves `source`. Node ids continue from `last_id`,
+/// which is moved past the new nodes, so that the resolutions of several
+/// inputs can be kept side by side. Interactive input may end in a bare
+/// expression without a `;`.
+pub(crate) fn analyze(
+    source: &str,
+    interactive: bool,
+    last_id: &mut usize,
+) -> Result<(Vec<Stmt>, HashMap<usize, Local>), LoxError> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens().map_err(LoxError::Scan)?;
+
+    let mut parser = if interactive {
+        Parser::for_session(scanner.tokens, *last_id)
+    } else {
+        Parser::continuing(scanner.tokens, *last_id)
+    };
+    let (statements, errors) = parser.parse();
+    *last_id = parser.last_id();
+    if !errors.is_empty() {
+        return Err(LoxError::Parse(errors));
+    }
+
+    let mut resolver = Resolver::new();
+    resolver.resolve(&statements).map_err(LoxError::Resolve)?;
+    Ok((statements, resolver.resolved_locals))
+}
+
+/// A Lox program that keeps its globals between calls to [`Session::eval`],
+/// for embedding the tree walking interpreter in a host application.
+///
+/// ```
+/// let mut session = rlox::Session::new();
+/// session.define_global("base", 40.0.into());
+/// session.eval("fun add(n) { return base + n; }").unwrap();
+/// let value = session.eval("add(2)").unwrap();
+/// assert_eq!(value.as_num(), Some(42.0));
+/// ```
+pub struct Session {
+    interpreter: Interpreter,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    #[must_use]
+    pub fn new() -> Self {
+        Session {
+            interpreter: Interpreter::new(Environment::new(), HashMap::new()),
+        }
+    }
+
+    /// Sends the output of `print` statements to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.interpreter.set_output(Box::new(output));
+    }
+
+    /// Defines or overwrites the global variable `name`.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.interpreter.define_global(name, value.0);
+    }
+
+    #[must_use]
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.get_global(name).map(Value)
+    }
+
+    /// Defines a global function implemented by the host. Calls with a
+    /// different number of arguments than `arity` fail before reaching it, and
+    /// an `Err` it returns becomes a runtime error at the call site.
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, move |args| {
+            let args: Vec<Value> = args.iter().cloned().map(Value).collect();
+            function(&args).map(|value| value.0)
+        });
+        self.interpreter
+            .define_global(name, RloxValue::NativeFunction(native));
+    }
+
+    /// Runs `source` and returns the value of its last statement if it is a
+    /// bare expression, which may omit its `;`, or `nil` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error that stopped the source from running. Globals
+    /// defined before a runtime error stay defined.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        self.run(source, true).map(Value)
+    }
+
+    /// Makes the imports of the sources run afterwards relative to the
+    /// directory of the file at `path`.
+    pub(crate) fn set_path(&mut self, path: &Path) {
+        self.interpreter.set_path(path);
+    }
+
+    /// Formats `value` the way `print` shows it, running `__str__` methods.
+    pub(crate) fn stringify(&mut self, value: &RloxValue) -> Result<String, LoxError> {
+        self.interpreter
+            .stringify(value)
+            .map_err(|e| LoxError::Runtime(e, self.interpreter.take_traceback()))
+    }
+
+    /// Runs `source`, interactively like [`Session::eval`] or as a whole file
+    /// whose statements all need their `;`.
+    pub(crate) fn run(&mut self, source: &str, interactive: bool) -> Result<RloxValue, LoxError> {
+        let statements = self.interpreter.analyze(source, interactive)?;
+        let result = if interactive {
+            self.interpreter.interpret_interactive(&statements)
+        } else {
+            self.interpreter
+                .interpret(&statements)
+                .map(|()| RloxValue::Nil)
+        };
+        result.map_err(|e| LoxError::Runtime(e, self.interpreter.take_traceback()))
+    }
+}
@@ -0,0 +1,74 @@
+use crate::token::RloxValue;
+
+/// A Lox value passed between a [`super::Session`] and its host. Numbers,
+/// strings, booleans and `nil` can be built and read back; any other value
+/// can only be handed back to the session or asked for its type.
+#[derive(Debug, Clone)]
+pub struct Value(pub(crate) RloxValue);
+
+impl Value {
+    #[must_use]
+    pub fn nil() -> Self {
+        Value(RloxValue::Nil)
+    }
+
+    #[must_use]
+    pub fn is_nil(&self) -> bool {
+        matches!(self.0, RloxValue::Nil)
+    }
+
+    #[must_use]
+    pub fn as_num(&self) -> Option<f64> {
+        match self.0 {
+            RloxValue::Num(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.0 {
+            RloxValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.0 {
+            RloxValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The name the `type` native gives the value, such as `"number"` or
+    /// `"instance"`.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.0.type_name()
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value(RloxValue::Num(n))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value(RloxValue::Bool(b))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value(RloxValue::Str(s.to_string()))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value(RloxValue::Str(s))
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{callable::Callable, class::RloxInstance};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+use crate::{
+    callable::{Callable, NativeFunction},
+    class::{RloxClass, RloxInstance, RloxTrait},
+    list::RloxList,
+    map::RloxMap,
+    module::RloxModule,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
@@ -6,7 +14,10 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
@@ -17,12 +28,16 @@ pub enum TokenType {
     BangEqual,
     Equal,
     EqualEqual,
+    Arrow,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
     Identifier,
     String,
+    /// The part of a string before an interpolated `${expression}`, the
+    /// expression's tokens follow it.
+    Interpolation,
     Number,
     And,
     Class,
@@ -40,6 +55,17 @@ pub enum TokenType {
     True,
     Var,
     While,
+    In,
+    Throw,
+    Try,
+    Catch,
+    Finally,
+    Static,
+    Trait,
+    With,
+    Import,
+    From,
+    As,
     Break,
     Continue,
     Eof,
@@ -63,9 +89,20 @@ impl TokenType {
             "true" => TokenType::True,
             "var" => TokenType::Var,
             "while" => TokenType::While,
+            "in" => TokenType::In,
+            "throw" => TokenType::Throw,
+            "try" => TokenType::Try,
+            "catch" => TokenType::Catch,
+            "finally" => TokenType::Finally,
+            "static" => TokenType::Static,
+            "trait" => TokenType::Trait,
+            "with" => TokenType::With,
+            "import" => TokenType::Import,
+            "from" => TokenType::From,
+            "as" => TokenType::As,
             "fun" => TokenType::Fun,
             "break" => TokenType::Break,
-            "continue" => TokenType::Break,
+            "continue" => TokenType::Continue,
             _ => TokenType::Identifier,
         }
     }
@@ -76,11 +113,37 @@ pub enum RloxValue {
     Str(String),
     Num(f64),
     Bool(bool),
+    Class(Rc<RloxClass>),
+    Trait(Rc<RloxTrait>),
+    Module(Rc<RloxModule>),
     Instance(RloxInstance),
+    List(RloxList),
+    Map(RloxMap),
     Callable(Callable),
+    NativeFunction(NativeFunction),
     Nil,
 }
 
+impl RloxValue {
+    /// The name `type()` returns for this value.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RloxValue::Str(_) => "string",
+            RloxValue::Num(_) => "number",
+            RloxValue::Bool(_) => "bool",
+            RloxValue::Class(_) => "class",
+            RloxValue::Trait(_) => "trait",
+            RloxValue::Module(_) => "module",
+            RloxValue::Instance(_) => "instance",
+            RloxValue::List(_) => "list",
+            RloxValue::Map(_) => "map",
+            RloxValue::Callable(_) | RloxValue::NativeFunction(_) => "function",
+            RloxValue::Nil => "nil",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum LiteralValue {
     Str(String),
@@ -93,8 +156,8 @@ impl LiteralValue {
     pub fn convert(&self) -> RloxValue {
         match self {
             LiteralValue::Str(s) => RloxValue::Str(s.clone()),
-            LiteralValue::Num(n) => RloxValue::Num(n.clone()),
-            LiteralValue::Bool(b) => RloxValue::Bool(b.clone()),
+            LiteralValue::Num(n) => RloxValue::Num(*n),
+            LiteralValue::Bool(b) => RloxValue::Bool(*b),
             LiteralValue::Nil => RloxValue::Nil,
         }
     }
@@ -103,9 +166,9 @@ impl LiteralValue {
 impl std::fmt::Display for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LiteralValue::Str(v) => write!(f, "{}", v),
-            LiteralValue::Num(v) => write!(f, "{}", v),
-            LiteralValue::Bool(v) => write!(f, "{}", v),
+            LiteralValue::Str(v) => write!(f, "{v}"),
+            LiteralValue::Num(v) => write!(f, "{v}"),
+            LiteralValue::Bool(v) => write!(f, "{v}"),
             LiteralValue::Nil => write!(f, "Nil"),
         }
     }
@@ -114,12 +177,55 @@ impl std::fmt::Display for LiteralValue {
 impl std::fmt::Display for RloxValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RloxValue::Str(v) => write!(f, "{}", v),
-            RloxValue::Num(v) => write!(f, "{}", v),
-            RloxValue::Bool(v) => write!(f, "{}", v),
+            RloxValue::Str(v) => write!(f, "{v}"),
+            RloxValue::Num(v) => write!(f, "{v}"),
+            RloxValue::Bool(v) => write!(f, "{v}"),
             RloxValue::Nil => write!(f, "Nil"),
-            RloxValue::Instance(i) => write!(f, "{}", i),
+            RloxValue::Class(c) => write!(f, "{c}"),
+            RloxValue::Trait(t) => write!(f, "{}", t.name),
+            RloxValue::Module(m) => write!(f, "{m}"),
+            RloxValue::Instance(i) => write!(f, "{i}"),
+            RloxValue::List(l) => write!(f, "{l}"),
+            RloxValue::Map(m) => write!(f, "{m}"),
             RloxValue::Callable(c) => write!(f, "{}", c.function.name.lexme),
+            RloxValue::NativeFunction(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+thread_local! {
+    /// The lists and maps being formatted, by address.
+    static FORMATTING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+/// Marks a list or map as being formatted until it is dropped, so that one
+/// containing itself is cut off instead of formatted forever.
+pub struct FormatGuard(usize);
+
+impl FormatGuard {
+    /// `None` if the value behind `ptr` is already being formatted.
+    pub fn enter<T>(ptr: *const T) -> Option<Self> {
+        let key = ptr.addr();
+        FORMATTING
+            .with(|f| f.borrow_mut().insert(key))
+            .then_some(FormatGuard(key))
+    }
+}
+
+impl Drop for FormatGuard {
+    fn drop(&mut self) {
+        FORMATTING.with(|f| f.borrow_mut().remove(&self.0));
+    }
+}
+
+/// Shows strings in quotes, the way they appear inside printed lists and maps.
+pub struct Quoted<'a>(pub &'a RloxValue);
+
+impl std::fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            RloxValue::Str(s) => write!(f, "{s:?}"),
+            v => write!(f, "{v}"),
         }
     }
 }
@@ -137,23 +243,19 @@ impl Token {
     pub fn eof_token(line: usize) -> Self {
         Token {
             r#type: TokenType::Eof,
-            lexme: "".to_string(),
+            lexme: String::new(),
             literal: None,
             column: 0,
             line,
         }
     }
-
-    fn to_string(&self) -> String {
-        match &self.literal {
-            Some(l) => format!("{:?} {} {:?}", self.r#type, self.lexme, l),
-            None => format!("{:?} {}", self.r#type, self.lexme),
-        }
-    }
 }
 
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        match &self.literal {
+            Some(l) => write!(f, "{:?} {} {:?}", self.r#type, self.lexme, l),
+            None => write!(f, "{:?} {}", self.r#type, self.lexme),
+        }
     }
 }
@@ -0,0 +1,121 @@
+use std::rc::Rc;
+
+use super::value::{ClassProto, Function, Value};
+
+/// A single VM instruction. Operands index into the chunk's constants, the
+/// current frame's locals or the current closure's upvalues.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(u16),
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal(u8),
+    SetLocal(u8),
+    GetGlobal(u16),
+    DefineGlobal(u16),
+    SetGlobal(u16),
+    GetUpvalue(u8),
+    SetUpvalue(u8),
+    /// Reads a property, binding it to the instance if it is a method.
+    GetProperty(u16),
+    /// Assigns a field, popping both the instance and the value.
+    SetProperty(u16),
+    /// Sets a field of a new instance to its default, unless a parameter of
+    /// the class already set it.
+    InitField(u16),
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    /// Prints the value on top of the stack unless it is `nil`, then pops it.
+    Echo,
+    Jump(u16),
+    JumpIfFalse(u16),
+    Loop(u16),
+    Call(u8),
+    /// Calls a property with the given number of arguments.
+    Invoke(u16, u8),
+    /// Reads a superclass method, bound to `this`.
+    GetSuper(u16),
+    SuperInvoke(u16, u8),
+    Closure(u16),
+    CloseUpvalue,
+    Return,
+    Class(u16),
+    Inherit(u16),
+    Method(u16),
+    /// Makes the closure on top of the stack the field defaults of the class
+    /// below it.
+    FieldDefaults,
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Num(f64),
+    Str(Rc<str>),
+    Function(Rc<Function>),
+    Class(Rc<ClassProto>),
+}
+
+/// Compiled code of one function, with the source position of every op for
+/// error reporting.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub positions: Vec<(usize, usize)>,
+    pub constants: Vec<Constant>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: Op, position: (usize, usize)) -> usize {
+        self.code.push(op);
+        self.positions.push(position);
+        self.code.len() - 1
+    }
+
+    /// Returns the index of `constant`, reusing an existing entry for equal
+    /// numbers and strings.
+    pub fn add_constant(&mut self, constant: Constant) -> Option<u16> {
+        let existing = self.constants.iter().position(|c| match (c, &constant) {
+            (Constant::Num(a), Constant::Num(b)) => a.to_bits() == b.to_bits(),
+            (Constant::Str(a), Constant::Str(b)) => a == b,
+            _ => false,
+        });
+        let index = existing.unwrap_or_else(|| {
+            self.constants.push(constant);
+            self.constants.len() - 1
+        });
+        u16::try_from(index).ok()
+    }
+
+    pub fn constant_value(&self, index: u16) -> Value {
+        match &self.constants[index as usize] {
+            Constant::Num(n) => Value::Num(*n),
+            Constant::Str(s) => Value::Str(s.clone()),
+            c => panic!("expected a value constant, found {c:?}"),
+        }
+    }
+
+    pub fn name(&self, index: u16) -> Rc<str> {
+        match &self.constants[index as usize] {
+            Constant::Str(s) => s.clone(),
+            c => panic!("expected a name constant, found {c:?}"),
+        }
+    }
+}
+
+#[test]
+pub fn ops_are_compact() {
+    assert!(std::mem::size_of::<Op>() <= 4);
+}
@@ -0,0 +1,895 @@
+use std::rc::Rc;
+
+#[allow(clippy::wildcard_imports)]
+use crate::{
+    ast::{expr::*, stmt::*},
+    errors::compiler_errors::CompilerError,
+    token::{LiteralValue, Token, TokenType},
+};
+
+use super::{
+    chunk::{Chunk, Constant, Op},
+    value::{ClassProto, Function, UpvalueRef},
+};
+
+type CompileResult = Result<(), CompilerError>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct LocalVar {
+    name: String,
+    depth: usize,
+    captured: bool,
+}
+
+struct LoopState {
+    continue_target: usize,
+    scope_depth: usize,
+    breaks: Vec<usize>,
+}
+
+/// Narrows a local or upvalue index, which the compiler keeps below 256.
+fn operand(index: usize) -> u8 {
+    u8::try_from(index).expect("index checked against the operand limit")
+}
+
+/// Where a declaration ends up once its value is on the stack.
+#[derive(Clone, Copy)]
+enum Target {
+    Global(u16),
+    NewLocal,
+    Local(u8),
+}
+
+struct FunctionState {
+    name: Rc<str>,
+    kind: FunctionKind,
+    position: (usize, usize),
+    arity: usize,
+    chunk: Chunk,
+    locals: Vec<LocalVar>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<LoopState>,
+}
+
+impl FunctionState {
+    fn new(name: &str, position: (usize, usize), kind: FunctionKind) -> Self {
+        // slot 0 holds the closure being run, or the receiver for methods
+        let reserved = LocalVar {
+            name: if matches!(kind, FunctionKind::Method | FunctionKind::Initializer) {
+                "this".to_string()
+            } else {
+                String::new()
+            },
+            depth: 0,
+            captured: false,
+        };
+        FunctionState {
+            name: name.into(),
+            kind,
+            position,
+            arity: 0,
+            chunk: Chunk::default(),
+            locals: vec![reserved],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+/// The first part of a class body the VM can't run, with the feature it
+/// belongs to.
+fn unsupported_member(stmt: &Class) -> Option<(&Token, &'static str)> {
+    let body = &stmt.body;
+    let statics = body.static_fields.iter().map(|f| &f.name);
+    if let Some(name) = statics
+        .chain(body.static_methods.iter().map(|m| &m.name))
+        .next()
+    {
+        return Some((name, "static members"));
+    }
+    if let Some(t) = stmt.traits.first() {
+        return Some((&t.name, "traits"));
+    }
+    if let Some(accessor) = body.getters.iter().chain(&body.setters).next() {
+        return Some((&accessor.name, "getters and setters"));
+    }
+    let overloads = |m: &&FnStmt| m.name.lexme.starts_with("__") && m.name.lexme.ends_with("__");
+    let method = body.methods.iter().find(overloads)?;
+    Some((&method.name, "operator overloading methods"))
+}
+
+/// Compiles a program that passed the resolver into bytecode for the
+/// [`super::Vm`].
+///
+/// Locals live on the VM stack and are addressed by their slot in the
+/// current call frame; variables captured by closures are reached through
+/// upvalues. Top-level declarations are globals looked up by name. The
+/// compiler works these slots out itself instead of reusing the resolver's
+/// `(depth, slot)` pairs: those index the tree walker's scope chain, which
+/// has a scope per block, while a call frame here is one flat window of the
+/// stack.
+///
+/// The VM runs this subset of the language:
+///
+/// - variables, blocks, `if`, `while`, `for`, `break` and `continue`
+/// - the arithmetic, comparison and logical operators on numbers, strings,
+///   booleans and `nil`
+/// - functions, closures and `return`
+/// - classes with methods, `init`, field declarations, class parameters,
+///   inheritance and `super`
+/// - the natives in [`super::natives`]
+///
+/// Lists, maps, for-in loops, exceptions, anonymous functions, string
+/// interpolation, static members, getters and setters, operator overloading,
+/// traits and imports fail to compile through [`Compiler::unsupported`]. This
+/// list and the README are kept in step with `VM_UNSUPPORTED` in the
+/// integration tests, so a feature the VM doesn't run is always rejected by
+/// name.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    position: (usize, usize),
+}
+
+impl Compiler {
+    pub fn compile(stmts: &[Stmt]) -> Result<Function, CompilerError> {
+        Compiler::compile_script(stmts, false)
+    }
+
+    /// Like [`Compiler::compile`], but the script also prints the value of
+    /// every bare expression statement unless it is an assignment or `nil`.
+    pub fn compile_interactive(stmts: &[Stmt]) -> Result<Function, CompilerError> {
+        Compiler::compile_script(stmts, true)
+    }
+
+    fn compile_script(stmts: &[Stmt], echo: bool) -> Result<Function, CompilerError> {
+        let mut compiler = Compiler {
+            functions: vec![FunctionState::new("script", (0, 0), FunctionKind::Script)],
+            position: (0, 0),
+        };
+        for s in stmts {
+            match s {
+                Stmt::Expresssion(e) if echo && !e.expr.is_assignment() => {
+                    compiler.expression(&e.expr)?;
+                    compiler.emit(Op::Echo);
+                }
+                _ => compiler.statement(s)?,
+            }
+        }
+        compiler.emit(Op::Nil);
+        compiler.emit(Op::Return);
+        let state = compiler.functions.pop().expect("script function");
+        Ok(Function {
+            name: state.name,
+            arity: 0,
+            chunk: state.chunk,
+            upvalues: state.upvalues,
+        })
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("no function being compiled")
+    }
+
+    fn at(&mut self, token: &Token) {
+        self.position = (token.line, token.column);
+    }
+
+    fn limit_error(&self, msg: &str) -> CompilerError {
+        CompilerError::limit_error(self.position.0, self.position.1, msg.to_string())
+    }
+
+    fn statement_error(&self, msg: &str) -> CompilerError {
+        CompilerError::statement_error(self.position.0, self.position.1, msg.to_string())
+    }
+
+    /// Rejects a language feature only the tree walker implements.
+    fn unsupported(&mut self, token: &Token, feature: &str) -> CompileResult {
+        self.at(token);
+        Err(CompilerError::unsupported_error(
+            self.position.0,
+            self.position.1,
+            format!("{feature} are not supported by the vm backend"),
+        ))
+    }
+
+    /// Returns `nil`, or the instance from an initializer.
+    fn emit_return(&mut self) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit(Op::GetLocal(0));
+        } else {
+            self.emit(Op::Nil);
+        }
+        self.emit(Op::Return);
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let position = self.position;
+        self.current().chunk.write(op, position)
+    }
+
+    fn make_constant(&mut self, constant: Constant) -> Result<u16, CompilerError> {
+        match self.current().chunk.add_constant(constant) {
+            Some(i) => Ok(i),
+            None => Err(self.limit_error("too many constants in one function")),
+        }
+    }
+
+    fn name_constant(&mut self, name: &str) -> Result<u16, CompilerError> {
+        self.make_constant(Constant::Str(name.into()))
+    }
+
+    fn emit_jump(&mut self, op: fn(u16) -> Op) -> usize {
+        self.emit(op(u16::MAX))
+    }
+
+    /// Points the jump at `at` to the next op to be emitted.
+    fn patch_jump(&mut self, at: usize) -> CompileResult {
+        let offset = self.current().chunk.code.len() - at - 1;
+        let Ok(offset) = u16::try_from(offset) else {
+            return Err(self.limit_error("too much code to jump over"));
+        };
+        let code = &mut self.current().chunk.code;
+        code[at] = match code[at] {
+            Op::Jump(_) => Op::Jump(offset),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(offset),
+            op => panic!("can't patch {op:?}"),
+        };
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> CompileResult {
+        let offset = self.current().chunk.code.len() - start + 1;
+        let Ok(offset) = u16::try_from(offset) else {
+            return Err(self.limit_error("loop body too large"));
+        };
+        self.emit(Op::Loop(offset));
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        while let Some(local) = self.current().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            let op = if local.captured {
+                Op::CloseUpvalue
+            } else {
+                Op::Pop
+            };
+            self.emit(op);
+            self.current().locals.pop();
+        }
+    }
+
+    /// Emits the pops that leave every scope deeper than `depth`, without
+    /// forgetting the locals, for jumps out of a loop body.
+    fn discard_locals(&mut self, depth: usize) {
+        let ops: Vec<Op> = self
+            .current()
+            .locals
+            .iter()
+            .rev()
+            .take_while(|l| l.depth > depth)
+            .map(|l| {
+                if l.captured {
+                    Op::CloseUpvalue
+                } else {
+                    Op::Pop
+                }
+            })
+            .collect();
+        for op in ops {
+            self.emit(op);
+        }
+    }
+
+    fn add_local(&mut self, name: &str) -> CompileResult {
+        if self.current().locals.len() > u8::MAX as usize {
+            return Err(self.limit_error("too many local variables in function"));
+        }
+        let state = self.current();
+        let depth = state.scope_depth;
+        state.locals.push(LocalVar {
+            name: name.to_string(),
+            depth,
+            captured: false,
+        });
+        Ok(())
+    }
+
+    /// Declares `name` in the current scope. Redeclaring a local in the same
+    /// scope reuses its slot.
+    fn declare_variable(&mut self, name: &str) -> Result<Target, CompilerError> {
+        let state = self.current();
+        if state.scope_depth == 0 {
+            return Ok(Target::Global(self.name_constant(name)?));
+        }
+        let depth = state.scope_depth;
+        let existing = state
+            .locals
+            .iter()
+            .rposition(|l| l.depth == depth && l.name == name);
+        if let Some(slot) = existing {
+            return Ok(Target::Local(operand(slot)));
+        }
+        self.add_local(name)?;
+        Ok(Target::NewLocal)
+    }
+
+    fn define_variable(&mut self, target: Target) {
+        match target {
+            Target::Global(name) => {
+                self.emit(Op::DefineGlobal(name));
+            }
+            Target::Local(slot) => {
+                self.emit(Op::SetLocal(slot));
+            }
+            Target::NewLocal => (),
+        }
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|l| l.name == name)
+            .map(operand)
+    }
+
+    fn resolve_upvalue(
+        &mut self,
+        function: usize,
+        name: &str,
+    ) -> Result<Option<u8>, CompilerError> {
+        if function == 0 {
+            return Ok(None);
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name) {
+            self.functions[function - 1].locals[slot as usize].captured = true;
+            return self.add_upvalue(function, slot, true).map(Some);
+        }
+        match self.resolve_upvalue(function - 1, name)? {
+            Some(index) => self.add_upvalue(function, index, false).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(
+        &mut self,
+        function: usize,
+        index: u8,
+        is_local: bool,
+    ) -> Result<u8, CompilerError> {
+        let upvalues = &mut self.functions[function].upvalues;
+        if let Some(i) = upvalues
+            .iter()
+            .position(|u| u.index == index && u.is_local == is_local)
+        {
+            return Ok(operand(i));
+        }
+        if upvalues.len() > u8::MAX as usize {
+            return Err(self.limit_error("too many closure variables in function"));
+        }
+        upvalues.push(UpvalueRef { index, is_local });
+        Ok(operand(upvalues.len() - 1))
+    }
+
+    fn named_variable(&mut self, name: &str, assign: bool) -> CompileResult {
+        let function = self.functions.len() - 1;
+        let op = if let Some(slot) = self.resolve_local(function, name) {
+            if assign {
+                Op::SetLocal(slot)
+            } else {
+                Op::GetLocal(slot)
+            }
+        } else if let Some(index) = self.resolve_upvalue(function, name)? {
+            if assign {
+                Op::SetUpvalue(index)
+            } else {
+                Op::GetUpvalue(index)
+            }
+        } else {
+            let name = self.name_constant(name)?;
+            if assign {
+                Op::SetGlobal(name)
+            } else {
+                Op::GetGlobal(name)
+            }
+        };
+        self.emit(op);
+        Ok(())
+    }
+
+    fn arguments(&mut self, args: &[Expr]) -> Result<u8, CompilerError> {
+        for arg in args {
+            self.expression(arg)?;
+        }
+        match u8::try_from(args.len()) {
+            Ok(n) => Ok(n),
+            Err(_) => Err(self.limit_error("can't have more than 255 arguments")),
+        }
+    }
+
+    fn literal(&mut self, expr: &Literal) -> CompileResult {
+        match &expr.value {
+            LiteralValue::Num(n) => {
+                let constant = self.make_constant(Constant::Num(*n))?;
+                self.emit(Op::Constant(constant));
+            }
+            LiteralValue::Str(s) => {
+                let constant = self.make_constant(Constant::Str(s.as_str().into()))?;
+                self.emit(Op::Constant(constant));
+            }
+            LiteralValue::Bool(true) => {
+                self.emit(Op::True);
+            }
+            LiteralValue::Bool(false) => {
+                self.emit(Op::False);
+            }
+            LiteralValue::Nil => {
+                self.emit(Op::Nil);
+            }
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self, expr: &Unary) -> CompileResult {
+        self.expression(&expr.right)?;
+        self.at(&expr.operator);
+        match expr.operator.r#type {
+            TokenType::Minus => self.emit(Op::Negate),
+            TokenType::Bang => self.emit(Op::Not),
+            _ => self.emit(Op::Nil),
+        };
+        Ok(())
+    }
+
+    fn binary(&mut self, expr: &Binary) -> CompileResult {
+        self.expression(&expr.left)?;
+        self.expression(&expr.right)?;
+        self.at(&expr.operator);
+        let op = match expr.operator.r#type {
+            TokenType::Minus => Op::Subtract,
+            TokenType::Slash => Op::Divide,
+            TokenType::Star => Op::Multiply,
+            TokenType::Plus => Op::Add,
+            TokenType::Greater => Op::Greater,
+            TokenType::GreaterEqual => Op::GreaterEqual,
+            TokenType::Less => Op::Less,
+            TokenType::LessEqual => Op::LessEqual,
+            TokenType::BangEqual => Op::NotEqual,
+            TokenType::EqualEqual => Op::Equal,
+            _ => {
+                self.emit(Op::Pop);
+                self.emit(Op::Pop);
+                Op::Nil
+            }
+        };
+        self.emit(op);
+        Ok(())
+    }
+
+    fn logical(&mut self, expr: &Logical) -> CompileResult {
+        self.expression(&expr.left)?;
+        self.at(&expr.operator);
+        match expr.operator.r#type {
+            TokenType::Or => {
+                let else_jump = self.emit_jump(Op::JumpIfFalse);
+                let end_jump = self.emit_jump(Op::Jump);
+                self.patch_jump(else_jump)?;
+                self.emit(Op::Pop);
+                self.expression(&expr.right)?;
+                self.patch_jump(end_jump)
+            }
+            TokenType::And => {
+                // a falsy left operand evaluates to `false`, not to itself
+                let false_jump = self.emit_jump(Op::JumpIfFalse);
+                self.emit(Op::Pop);
+                self.expression(&expr.right)?;
+                let end_jump = self.emit_jump(Op::Jump);
+                self.patch_jump(false_jump)?;
+                self.emit(Op::Pop);
+                self.emit(Op::False);
+                self.patch_jump(end_jump)
+            }
+            _ => {
+                Err(self
+                    .statement_error(&format!("invalid logical operator {}", expr.operator.lexme)))
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) -> CompileResult {
+        match expr {
+            Expr::Literal(l) => self.literal(l),
+            Expr::Grouping(g) => self.expression(&g.expression),
+            Expr::Unary(u) => self.unary(u),
+            Expr::Binary(b) => self.binary(b),
+            Expr::Logical(l) => self.logical(l),
+            Expr::Variable(v) => {
+                self.at(&v.name);
+                self.named_variable(&v.name.lexme, false)
+            }
+            Expr::Assign(a) => {
+                self.expression(&a.value)?;
+                self.at(&a.name);
+                self.named_variable(&a.name.lexme, true)?;
+                self.emit(Op::Nil);
+                Ok(())
+            }
+            Expr::Call(c) => {
+                self.expression(&c.callee)?;
+                let argc = self.arguments(&c.args)?;
+                self.at(&c.paren);
+                self.emit(Op::Call(argc));
+                Ok(())
+            }
+            Expr::Get(g) => {
+                self.expression(&g.object)?;
+                let argc = match &g.method_args {
+                    Some(args) => Some(self.arguments(args)?),
+                    None => None,
+                };
+                self.at(&g.name);
+                let name = self.name_constant(&g.name.lexme)?;
+                self.emit(match argc {
+                    Some(argc) => Op::Invoke(name, argc),
+                    None => Op::GetProperty(name),
+                });
+                Ok(())
+            }
+            Expr::Set(s) => {
+                self.expression(&s.object)?;
+                self.expression(&s.value)?;
+                self.at(&s.name);
+                let name = self.name_constant(&s.name.lexme)?;
+                self.emit(Op::SetProperty(name));
+                self.emit(Op::Nil);
+                Ok(())
+            }
+            Expr::This(t) => {
+                self.at(&t.keyword);
+                self.named_variable("this", false)
+            }
+            Expr::Super(s) => {
+                self.at(&s.keyword);
+                self.named_variable("this", false)?;
+                let argc = match &s.method_args {
+                    Some(args) => Some(self.arguments(args)?),
+                    None => None,
+                };
+                self.at(&s.keyword);
+                self.named_variable("super", false)?;
+                self.at(&s.method);
+                let name = self.name_constant(&s.method.lexme)?;
+                self.emit(match argc {
+                    Some(argc) => Op::SuperInvoke(name, argc),
+                    None => Op::GetSuper(name),
+                });
+                Ok(())
+            }
+            Expr::List(l) => self.unsupported(&l.bracket, "lists"),
+            Expr::Interpolation(i) => self.unsupported(&i.quote, "interpolated strings"),
+            Expr::Map(m) => self.unsupported(&m.brace, "maps"),
+            Expr::Lambda(l) => self.unsupported(&l.function.name, "anonymous functions"),
+            Expr::Index(i) => self.unsupported(&i.bracket, "lists and maps"),
+            Expr::SetIndex(s) => self.unsupported(&s.bracket, "lists and maps"),
+        }
+    }
+
+    fn var_declaration(&mut self, stmt: &VarStmt) -> CompileResult {
+        self.at(&stmt.name);
+        let target = self.declare_variable(&stmt.name.lexme)?;
+        self.expression(&stmt.initializer)?;
+        self.at(&stmt.name);
+        self.define_variable(target);
+        Ok(())
+    }
+
+    fn function(&mut self, stmt: &FnStmt, kind: FunctionKind) -> CompileResult {
+        self.at(&stmt.name);
+        let position = self.position;
+        self.functions
+            .push(FunctionState::new(&stmt.name.lexme, position, kind));
+        self.begin_scope();
+        for param in &stmt.params {
+            self.at(param);
+            self.add_local(&param.lexme)?;
+        }
+        self.current().arity = stmt.params.len();
+        for s in &stmt.body {
+            self.statement(s)?;
+        }
+        self.emit_return();
+        self.emit_closure()
+    }
+
+    /// Compiles the field defaults of a class into a method setting them on
+    /// `this`. They are evaluated where the class is declared.
+    fn field_defaults(&mut self, stmt: &Class) -> CompileResult {
+        self.at(&stmt.name);
+        let position = self.position;
+        self.functions.push(FunctionState::new(
+            &stmt.name.lexme,
+            position,
+            FunctionKind::Method,
+        ));
+        for field in &stmt.body.fields {
+            self.at(&field.name);
+            self.emit(Op::GetLocal(0));
+            self.expression(&field.initializer)?;
+            let name = self.name_constant(&field.name.lexme)?;
+            self.at(&field.name);
+            self.emit(Op::InitField(name));
+        }
+        self.emit_return();
+        self.emit_closure()
+    }
+
+    /// Finishes the function being compiled and emits its closure.
+    fn emit_closure(&mut self) -> CompileResult {
+        let state = self.functions.pop().expect("function being compiled");
+        let position = state.position;
+        let function = Function {
+            name: state.name,
+            arity: state.arity,
+            chunk: state.chunk,
+            upvalues: state.upvalues,
+        };
+        let constant = self.make_constant(Constant::Function(Rc::new(function)))?;
+        self.position = position;
+        self.emit(Op::Closure(constant));
+        Ok(())
+    }
+
+    fn fun_declaration(&mut self, stmt: &FnStmt) -> CompileResult {
+        self.at(&stmt.name);
+        let target = self.declare_variable(&stmt.name.lexme)?;
+        self.function(stmt, FunctionKind::Function)?;
+        self.define_variable(target);
+        Ok(())
+    }
+
+    fn class_declaration(&mut self, stmt: &Class) -> CompileResult {
+        if let Some((token, feature)) = unsupported_member(stmt) {
+            return self.unsupported(token, feature);
+        }
+        let body = &stmt.body;
+        self.at(&stmt.name);
+        let name = &stmt.name.lexme;
+        let proto = ClassProto {
+            name: name.as_str().into(),
+            params: stmt
+                .params
+                .iter()
+                .map(|p| p.lexme.as_str().into())
+                .collect(),
+        };
+        let proto = self.make_constant(Constant::Class(Rc::new(proto)))?;
+        let target = self.declare_variable(name)?;
+        self.emit(Op::Class(proto));
+        self.define_variable(target);
+
+        if let Some(superclass) = &stmt.superclass {
+            self.at(&superclass.name);
+            self.named_variable(&superclass.name.lexme, false)?;
+            self.begin_scope();
+            self.add_local("super")?;
+            self.named_variable(name, false)?;
+            let superclass_name = self.name_constant(&superclass.name.lexme)?;
+            self.emit(Op::Inherit(superclass_name));
+        }
+
+        self.named_variable(name, false)?;
+        for method in &body.methods {
+            let kind = if method.name.lexme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(method, kind)?;
+            let method_name = self.name_constant(&method.name.lexme)?;
+            self.emit(Op::Method(method_name));
+        }
+        if !body.fields.is_empty() {
+            self.field_defaults(stmt)?;
+            self.emit(Op::FieldDefaults);
+        }
+        self.emit(Op::Pop);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        Ok(())
+    }
+
+    /// Compiles the body of an `if` or a loop. A declaration used directly as
+    /// the body gets a scope of its own so that it can't leave the stack
+    /// unbalanced when it is skipped.
+    fn body_statement(&mut self, stmt: &Stmt) -> CompileResult {
+        let declares = matches!(stmt, Stmt::Var(_) | Stmt::FnStmt(_) | Stmt::Class(_));
+        if !declares || self.current().scope_depth == 0 {
+            return self.statement(stmt);
+        }
+        self.begin_scope();
+        self.statement(stmt)?;
+        self.end_scope();
+        Ok(())
+    }
+
+    fn if_statement(&mut self, stmt: &IfStmt) -> CompileResult {
+        self.expression(&stmt.condition)?;
+        let then_jump = self.emit_jump(Op::JumpIfFalse);
+        self.emit(Op::Pop);
+        self.body_statement(&stmt.then_branch)?;
+        let else_jump = self.emit_jump(Op::Jump);
+        self.patch_jump(then_jump)?;
+        self.emit(Op::Pop);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.body_statement(else_branch)?;
+        }
+        self.patch_jump(else_jump)
+    }
+
+    fn loop_body(
+        &mut self,
+        body: &Stmt,
+        continue_target: usize,
+    ) -> Result<Vec<usize>, CompilerError> {
+        let scope_depth = self.current().scope_depth;
+        self.current().loops.push(LoopState {
+            continue_target,
+            scope_depth,
+            breaks: Vec::new(),
+        });
+        let result = self.body_statement(body);
+        let state = self.current().loops.pop().expect("loop being compiled");
+        result.map(|()| state.breaks)
+    }
+
+    fn while_statement(&mut self, stmt: &WhileStmt) -> CompileResult {
+        let loop_start = self.current().chunk.code.len();
+        self.expression(&stmt.condition)?;
+        let exit_jump = self.emit_jump(Op::JumpIfFalse);
+        self.emit(Op::Pop);
+        let breaks = self.loop_body(&stmt.body, loop_start)?;
+        self.emit_loop(loop_start)?;
+        self.patch_jump(exit_jump)?;
+        self.emit(Op::Pop);
+        for jump in breaks {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    fn for_statement(&mut self, stmt: &ForStmt) -> CompileResult {
+        self.begin_scope();
+        match &stmt.initializer {
+            Some(ForStmtInitializer::VarDecl(v)) => self.var_declaration(v)?,
+            Some(ForStmtInitializer::ExprStmt(e)) => {
+                self.expression(&e.expr)?;
+                self.emit(Op::Pop);
+            }
+            None => (),
+        }
+
+        let mut loop_start = self.current().chunk.code.len();
+        let mut exit_jump = None;
+        if let Some(condition) = &stmt.condition {
+            self.expression(condition)?;
+            exit_jump = Some(self.emit_jump(Op::JumpIfFalse));
+            self.emit(Op::Pop);
+        }
+        if let Some(afterthought) = &stmt.afterthought {
+            let body_jump = self.emit_jump(Op::Jump);
+            let increment_start = self.current().chunk.code.len();
+            self.expression(afterthought)?;
+            self.emit(Op::Pop);
+            self.emit_loop(loop_start)?;
+            loop_start = increment_start;
+            self.patch_jump(body_jump)?;
+        }
+
+        let breaks = self.loop_body(&stmt.body, loop_start)?;
+        self.emit_loop(loop_start)?;
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump)?;
+            self.emit(Op::Pop);
+        }
+        for jump in breaks {
+            self.patch_jump(jump)?;
+        }
+        self.end_scope();
+        Ok(())
+    }
+
+    fn break_statement(&mut self) -> CompileResult {
+        let Some(depth) = self.current().loops.last().map(|l| l.scope_depth) else {
+            return Err(self.statement_error("can't use \"break\" outside of a loop"));
+        };
+        self.discard_locals(depth);
+        let jump = self.emit_jump(Op::Jump);
+        if let Some(l) = self.current().loops.last_mut() {
+            l.breaks.push(jump);
+        }
+        Ok(())
+    }
+
+    fn continue_statement(&mut self) -> CompileResult {
+        let Some((depth, target)) = self
+            .current()
+            .loops
+            .last()
+            .map(|l| (l.scope_depth, l.continue_target))
+        else {
+            return Err(self.statement_error("can't use \"continue\" outside of a loop"));
+        };
+        self.discard_locals(depth);
+        self.emit_loop(target)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> CompileResult {
+        match stmt {
+            Stmt::Expresssion(e) => {
+                self.expression(&e.expr)?;
+                self.emit(Op::Pop);
+                Ok(())
+            }
+            Stmt::Print(p) => {
+                self.expression(&p.expr)?;
+                self.emit(Op::Print);
+                Ok(())
+            }
+            Stmt::Var(v) => self.var_declaration(v),
+            Stmt::Block(b) => {
+                self.begin_scope();
+                for s in &b.statements {
+                    self.statement(s)?;
+                }
+                self.end_scope();
+                Ok(())
+            }
+            Stmt::IfStmt(i) => self.if_statement(i),
+            Stmt::WhileStmt(w) => self.while_statement(w),
+            Stmt::ForStmt(f) => self.for_statement(f),
+            Stmt::ForInStmt(f) => self.unsupported(&f.keyword, "for-in loops"),
+            Stmt::ThrowStmt(t) => self.unsupported(&t.keyword, "exceptions"),
+            Stmt::TryStmt(t) => self.unsupported(&t.keyword, "exceptions"),
+            Stmt::FnStmt(f) => self.fun_declaration(f),
+            Stmt::ReturnStmt(r) => {
+                self.at(&r.keyword);
+                match &r.value {
+                    Some(v) => {
+                        self.expression(v)?;
+                        self.emit(Op::Return);
+                    }
+                    None => self.emit_return(),
+                }
+                Ok(())
+            }
+            Stmt::BreakStmt(_) => self.break_statement(),
+            Stmt::ContStmt(_) => self.continue_statement(),
+            Stmt::Class(c) => self.class_declaration(c),
+            Stmt::Trait(t) => self.unsupported(&t.name, "traits"),
+            Stmt::Import(i) => self.unsupported(&i.keyword, "imports"),
+        }
+    }
+}
@@ -0,0 +1,619 @@
+pub mod chunk;
+pub mod compiler;
+mod natives;
+pub mod value;
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::errors::{interpreter_errors::RuntimeError, StackFrame};
+
+use self::{
+    chunk::{Chunk, Constant, Op},
+    value::{BoundMethod, Class, Closure, Function, Instance, Upvalue, Value},
+};
+
+/// How deep calls nest before they fail with a stack overflow, on both
+/// backends.
+pub(crate) const MAX_FRAMES: usize = 1024;
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base: usize,
+    /// Field defaults are run for their effect, their result is dropped.
+    discard_result: bool,
+}
+
+/// A stack machine running the bytecode produced by [`compiler::Compiler`].
+///
+/// Values are reference counted, so closures that capture themselves (a
+/// local function calling itself) are only freed when the `Vm` is dropped.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    /// The calls that were running when the last runtime error was raised.
+    traceback: Vec<StackFrame>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    #[must_use]
+    pub fn new() -> Self {
+        Vm {
+            stack: Vec::with_capacity(256),
+            frames: Vec::with_capacity(64),
+            globals: natives::natives(),
+            open_upvalues: Vec::new(),
+            traceback: Vec::new(),
+        }
+    }
+
+    pub fn interpret(&mut self, script: Function) -> Result<(), RuntimeError> {
+        let closure = Rc::new(Closure {
+            function: Rc::new(script),
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: 0,
+            discard_result: false,
+        });
+        let result = self.run();
+        if result.is_err() {
+            self.traceback = self.traceback();
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    /// Takes the calls that led to the last runtime error, innermost first.
+    pub fn take_traceback(&mut self) -> Vec<StackFrame> {
+        std::mem::take(&mut self.traceback)
+    }
+
+    /// The calls on the frame stack, innermost first, each with the position
+    /// its caller was at. The script itself is not a call, nor is an `init`
+    /// waiting for the field defaults above it.
+    fn traceback(&self) -> Vec<StackFrame> {
+        let started: Vec<&CallFrame> = self.frames.iter().filter(|f| f.ip > 0).collect();
+        started
+            .windows(2)
+            .rev()
+            .map(|pair| {
+                let (caller, frame) = (&pair[0], &pair[1]);
+                let (line, column) = caller.closure.function.chunk.positions[caller.ip - 1];
+                StackFrame {
+                    function: frame.closure.function.name.to_string(),
+                    line,
+                    column,
+                }
+            })
+            .collect()
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("no call frame")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("no call frame")
+    }
+
+    fn chunk(&self) -> &Chunk {
+        &self.frame().closure.function.chunk
+    }
+
+    /// Source position of the op being run.
+    fn position(&self) -> (usize, usize) {
+        let frame = self.frame();
+        frame.closure.function.chunk.positions[frame.ip - 1]
+    }
+
+    fn error(&self, msg: &str) -> RuntimeError {
+        let (line, column) = self.position();
+        RuntimeError::value_error(line, column, msg.to_string())
+    }
+
+    fn operand_error(&self, msg: &str) -> RuntimeError {
+        let (line, column) = self.position();
+        RuntimeError::operand_error(line, column, msg.to_string())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn binary_num(
+        &mut self,
+        operator: &str,
+        f: impl Fn(f64, f64) -> Value,
+    ) -> Result<(), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+        let result = match (&left, &right) {
+            (Value::Num(n1), Value::Num(n2)) => f(*n1, *n2),
+            _ => {
+                return Err(self.operand_error(&format!(
+                    "operands of '{operator}' must be numbers, found {} and {}",
+                    left.type_name(),
+                    right.type_name()
+                )))
+            }
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            let frame = self.frame_mut();
+            let op = frame.closure.function.chunk.code[frame.ip];
+            frame.ip += 1;
+            match op {
+                Op::Constant(i) => {
+                    let value = self.chunk().constant_value(i);
+                    self.stack.push(value);
+                }
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[self.frame().base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.pop();
+                    let base = self.frame().base;
+                    self.stack[base + slot as usize] = value;
+                }
+                Op::GetGlobal(i) => {
+                    let name = self.chunk().name(i);
+                    let value = self.globals.get(&name).cloned().unwrap_or(Value::Nil);
+                    self.stack.push(value);
+                }
+                Op::DefineGlobal(i) => {
+                    let name = self.chunk().name(i);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                Op::SetGlobal(i) => {
+                    let name = self.chunk().name(i);
+                    let value = self.pop();
+                    match self.globals.get_mut(&name) {
+                        Some(v) => *v = value,
+                        None => {
+                            return Err(
+                                self.error(&format!("cannot find variable {name} in this scope"))
+                            )
+                        }
+                    }
+                }
+                Op::GetUpvalue(i) => {
+                    let upvalue = self.frame().closure.upvalues[i as usize].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(v) => v.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetUpvalue(i) => {
+                    let value = self.pop();
+                    let upvalue = self.frame().closure.upvalues[i as usize].clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(v) => *v = value,
+                    }
+                }
+                Op::SetProperty(i) => {
+                    let name = self.chunk().name(i);
+                    let value = self.pop();
+                    let object = self.pop();
+                    let Value::Instance(instance) = object else {
+                        return Err(self.error("only instances have properties"));
+                    };
+                    match instance.borrow_mut().fields.get_mut(&name) {
+                        Some(field) => *field = value,
+                        None => return Err(self.error("undefined property")),
+                    };
+                }
+                Op::InitField(i) => {
+                    let name = self.chunk().name(i);
+                    let value = self.pop();
+                    let Value::Instance(instance) = self.pop() else {
+                        panic!("expected a new instance");
+                    };
+                    let mut instance = instance.borrow_mut();
+                    if !instance.class.params.borrow().contains(&name) {
+                        instance.fields.insert(name, value);
+                    }
+                }
+                Op::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left.is_equal(&right)));
+                }
+                Op::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(!left.is_equal(&right)));
+                }
+                Op::Greater => self.binary_num(">", |a, b| Value::Bool(a > b))?,
+                Op::GreaterEqual => self.binary_num(">=", |a, b| Value::Bool(a >= b))?,
+                Op::Less => self.binary_num("<", |a, b| Value::Bool(a < b))?,
+                Op::LessEqual => self.binary_num("<=", |a, b| Value::Bool(a <= b))?,
+                Op::Subtract => self.binary_num("-", |a, b| Value::Num(a - b))?,
+                Op::Multiply => self.binary_num("*", |a, b| Value::Num(a * b))?,
+                Op::Divide => {
+                    if let (Value::Num(_), Value::Num(n)) = (self.peek(1), self.peek(0)) {
+                        if *n == 0.0 {
+                            let (line, column) = self.position();
+                            return Err(RuntimeError::zero_division_error(line, column));
+                        }
+                    }
+                    self.binary_num("/", |a, b| Value::Num(a / b))?;
+                }
+                Op::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = match (&left, &right) {
+                        (Value::Num(n1), Value::Num(n2)) => Value::Num(n1 + n2),
+                        (Value::Str(s1), Value::Str(s2)) => Value::Str(format!("{s1}{s2}").into()),
+                        _ => {
+                            return Err(self.operand_error(&format!(
+                            "operands of '+' must be two numbers or two strings, found {} and {}",
+                            left.type_name(),
+                            right.type_name()
+                        )))
+                        }
+                    };
+                    self.stack.push(result);
+                }
+                Op::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                Op::Negate => {
+                    let value = match self.pop() {
+                        Value::Num(n) => Value::Num(-n),
+                        v => {
+                            return Err(self.operand_error(&format!(
+                                "operand of '-' must be a number, found {}",
+                                v.type_name()
+                            )))
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                Op::Print => {
+                    let value = self.pop();
+                    println!("{value}");
+                }
+                Op::Echo => {
+                    let value = self.pop();
+                    if !matches!(value, Value::Nil) {
+                        println!("{value}");
+                    }
+                }
+                Op::Jump(offset) => self.frame_mut().ip += offset as usize,
+                Op::JumpIfFalse(offset) => {
+                    if !self.peek(0).is_truthy() {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                Op::Loop(offset) => self.frame_mut().ip -= offset as usize,
+                Op::Call(argc) => {
+                    let callee = self.peek(argc as usize).clone();
+                    self.call_value(callee, argc as usize)?;
+                }
+                Op::GetProperty(i) => {
+                    let name = self.chunk().name(i);
+                    self.get_property(&name)?;
+                }
+                Op::Invoke(i, argc) => {
+                    let name = self.chunk().name(i);
+                    self.invoke(&name, argc as usize)?;
+                }
+                Op::GetSuper(i) => {
+                    let name = self.chunk().name(i);
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.error("can't use \"super\" in a class with no superclass"));
+                    };
+                    let Some(method) = superclass.find_method(&name) else {
+                        return Err(self.error(&format!("undefined superclass method {name}")));
+                    };
+                    let receiver = self.pop();
+                    self.stack.push(Value::BoundMethod(Rc::new(BoundMethod {
+                        receiver,
+                        method,
+                    })));
+                }
+                Op::SuperInvoke(i, argc) => {
+                    let name = self.chunk().name(i);
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.error("can't use \"super\" in a class with no superclass"));
+                    };
+                    match superclass.find_method(&name) {
+                        Some(method) => self.call_closure(method, argc as usize)?,
+                        None => {
+                            return Err(self.error(&format!("undefined superclass method {name}")))
+                        }
+                    }
+                }
+                Op::Closure(i) => {
+                    let Constant::Function(function) = &self.chunk().constants[i as usize] else {
+                        panic!("expected a function constant");
+                    };
+                    let function = function.clone();
+                    let base = self.frame().base;
+                    let upvalues = function
+                        .upvalues
+                        .iter()
+                        .map(|u| {
+                            if u.is_local {
+                                self.capture_upvalue(base + u.index as usize)
+                            } else {
+                                self.frame().closure.upvalues[u.index as usize].clone()
+                            }
+                        })
+                        .collect();
+                    let closure = Closure { function, upvalues };
+                    self.stack.push(Value::Closure(Rc::new(closure)));
+                }
+                Op::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                Op::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("no call frame");
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    if !frame.discard_result {
+                        self.stack.push(result);
+                    }
+                }
+                Op::Class(i) => {
+                    let Constant::Class(proto) = &self.chunk().constants[i as usize] else {
+                        panic!("expected a class constant");
+                    };
+                    let class = Class::new(proto);
+                    self.stack.push(Value::Class(Rc::new(class)));
+                }
+                Op::Inherit(i) => {
+                    let Value::Class(class) = self.pop() else {
+                        panic!("expected a class to inherit into");
+                    };
+                    let Value::Class(superclass) = self.peek(0).clone() else {
+                        let name = self.chunk().name(i);
+                        return Err(self.error(&format!("superclass {name} must be a class")));
+                    };
+                    if class.params.borrow().is_empty() {
+                        class.params.replace(superclass.params.borrow().clone());
+                    }
+                    class.superclass.replace(Some(superclass));
+                }
+                Op::Method(i) => {
+                    let name = self.chunk().name(i);
+                    let Value::Closure(method) = self.pop() else {
+                        panic!("expected a method closure");
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        panic!("expected a class to add a method to");
+                    };
+                    class.methods.borrow_mut().insert(name, method);
+                }
+                Op::FieldDefaults => {
+                    let Value::Closure(defaults) = self.pop() else {
+                        panic!("expected a field defaults closure");
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        panic!("expected a class to add field defaults to");
+                    };
+                    class.defaults.replace(Some(defaults));
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argc: usize) -> Result<(), RuntimeError> {
+        match callee {
+            Value::Closure(c) => self.call_closure(c, argc),
+            Value::BoundMethod(b) => {
+                let receiver = self.stack.len() - argc - 1;
+                self.stack[receiver] = b.receiver.clone();
+                self.call_closure(b.method.clone(), argc)
+            }
+            Value::Native(native) => {
+                if native.arity != argc {
+                    return Err(self.error(&format!(
+                        "this function takes {} args but {argc} were given",
+                        native.arity
+                    )));
+                }
+                let mut arguments = self.stack.split_off(self.stack.len() - argc);
+                self.pop();
+                if native.formats_args {
+                    for argument in &mut arguments {
+                        *argument = Value::Str(argument.to_string().into());
+                    }
+                }
+                let result = (native.function)(&arguments).map_err(|e| self.error(&e))?;
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Class(class) => self.instantiate(class, argc),
+            v => Err(self.error(&format!(
+                "can only call functions and classes, found {}",
+                v.type_name()
+            ))),
+        }
+    }
+
+    /// Replaces the class below the arguments with a new instance, passing
+    /// the arguments to `init` or to the parameters of the class. The field
+    /// defaults run first, those of the outermost superclass first.
+    fn instantiate(&mut self, class: Rc<Class>, argc: usize) -> Result<(), RuntimeError> {
+        let init = class.find_method("init");
+        let arity = init
+            .as_ref()
+            .map_or_else(|| class.params.borrow().len(), |i| i.function.arity);
+        if argc != arity {
+            let callee = if init.is_some() {
+                "this function".to_string()
+            } else {
+                class.name.to_string()
+            };
+            return Err(self.error(&format!(
+                "{callee} takes {arity} args but {argc} were given"
+            )));
+        }
+        let receiver = self.stack.len() - argc - 1;
+        let fields = if init.is_some() {
+            HashMap::new()
+        } else {
+            let arguments = self.stack.split_off(receiver + 1);
+            class
+                .params
+                .borrow()
+                .iter()
+                .cloned()
+                .zip(arguments)
+                .collect()
+        };
+        let instance = Instance {
+            class: class.clone(),
+            fields,
+        };
+        let instance = Value::Instance(Rc::new(RefCell::new(instance)));
+        self.stack[receiver] = instance.clone();
+        if let Some(init) = init {
+            self.call_closure(init, argc)?;
+        }
+        // frames run last pushed first
+        let classes = std::iter::successors(Some(class), |c| c.superclass.borrow().clone());
+        for class in classes {
+            if let Some(defaults) = class.defaults.borrow().clone() {
+                self.stack.push(instance.clone());
+                self.call_closure(defaults, 0)?;
+                self.frame_mut().discard_result = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn call_closure(&mut self, closure: Rc<Closure>, argc: usize) -> Result<(), RuntimeError> {
+        let function = &closure.function;
+        if function.arity != argc {
+            return Err(self.error(&format!(
+                "this function takes {} args but {argc} were given",
+                function.arity
+            )));
+        }
+        if self.frames.len() == MAX_FRAMES {
+            return Err(self.error("stack overflow"));
+        }
+        let base = self.stack.len() - argc - 1;
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base,
+            discard_result: false,
+        });
+        Ok(())
+    }
+
+    /// Reads property `name` of the receiver below the arguments, calling it
+    /// if it is a method.
+    fn get_property(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let Value::Instance(instance) = self.peek(0).clone() else {
+            return Err(self.error("only instances have properties"));
+        };
+        if let Some(field) = instance.borrow().fields.get(name) {
+            self.pop();
+            self.stack.push(field.clone());
+            return Ok(());
+        }
+        let method = instance.borrow().class.find_method(name);
+        match method {
+            Some(method) => {
+                let receiver = self.pop();
+                self.stack.push(Value::BoundMethod(Rc::new(BoundMethod {
+                    receiver,
+                    method,
+                })));
+                Ok(())
+            }
+            None => Err(self.error("undefined property")),
+        }
+    }
+
+    fn invoke(&mut self, name: &str, argc: usize) -> Result<(), RuntimeError> {
+        let Value::Instance(instance) = self.peek(argc).clone() else {
+            return Err(self.error("only instances have properties"));
+        };
+        let field = instance.borrow().fields.get(name).cloned();
+        if let Some(field) = field {
+            // the function takes the receiver's place, like a plain call
+            let receiver = self.stack.len() - argc - 1;
+            self.stack[receiver] = field.clone();
+            return self.call_value(field, argc);
+        }
+        let method = instance.borrow().class.find_method(name);
+        match method {
+            Some(method) => self.call_closure(method, argc),
+            None => Err(self.error("undefined property")),
+        }
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|u| matches!(&*u.borrow(), Upvalue::Open(s) if *s == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves every captured variable at or above stack index `from` into its
+    /// upvalue, as the scope holding it is about to go away.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= from => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+}
@@ -0,0 +1,62 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::natives::{self, NativeValue};
+
+use super::value::Value;
+
+/// The built-in functions, keyed by their global name. They are the tree
+/// walker's in [`crate::natives`], seeing VM values through [`NativeValue`].
+pub fn natives() -> HashMap<Rc<str>, Value> {
+    natives::natives()
+        .into_iter()
+        .map(|n| (Rc::from(n.name), Value::Native(Rc::new(n))))
+        .collect()
+}
+
+impl NativeValue for Value {
+    fn nil() -> Self {
+        Value::Nil
+    }
+
+    fn from_bool(b: bool) -> Self {
+        Value::Bool(b)
+    }
+
+    fn from_num(n: f64) -> Self {
+        Value::Num(n)
+    }
+
+    fn from_string(s: String) -> Self {
+        Value::Str(s.into())
+    }
+
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn len(&self) -> Option<usize> {
+        match self {
+            Value::Str(s) => Some(s.chars().count()),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        Value::type_name(self)
+    }
+
+    // the vm has no traits
+    fn implements(&self, _: &Self) -> Option<bool> {
+        None
+    }
+}
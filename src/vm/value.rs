@@ -0,0 +1,154 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::natives;
+
+use super::chunk::Chunk;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Num(f64),
+    Str(Rc<str>),
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    Native(Rc<Native>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::Nil => false,
+            _ => true,
+        }
+    }
+
+    #[allow(clippy::float_cmp)]
+    pub fn is_equal(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Num(n1), Value::Num(n2)) => n1 == n2,
+            (Value::Str(s1), Value::Str(s2)) => s1 == s2,
+            (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
+            (Value::Class(c1), Value::Class(c2)) => Rc::ptr_eq(c1, c2),
+            (Value::Instance(i1), Value::Instance(i2)) => Rc::ptr_eq(i1, i2),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "string",
+            Value::Num(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Instance(_) => "instance",
+            Value::Closure(_) | Value::BoundMethod(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Nil => "nil",
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "Nil"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Num(n) => write!(f, "{n}"),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Closure(c) => write!(f, "{}", c.function.name),
+            Value::BoundMethod(b) => write!(f, "{}", b.method.function.name),
+            Value::Native(n) => write!(f, "<native fn {}>", n.name),
+            Value::Class(c) => write!(f, "{}", c.name),
+            Value::Instance(i) => write!(f, "{} instance", i.borrow().class.name),
+        }
+    }
+}
+
+/// Where a closure finds a captured variable when it is created: in a local
+/// slot of the enclosing function or in one of the enclosing closure's upvalues.
+#[derive(Debug, Clone, Copy)]
+pub struct UpvalueRef {
+    pub index: u8,
+    pub is_local: bool,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: Rc<str>,
+    pub arity: usize,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueRef>,
+}
+
+/// A built-in function, shared with the tree walker.
+pub type Native = natives::Native<Value>;
+
+/// A captured variable. It points into the stack while the variable is in
+/// scope and holds the value itself once the scope is gone.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A method read from an instance without calling it, `receiver` becomes
+/// `this` when it is called.
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+/// What the compiler knows about a class before it runs.
+#[derive(Debug)]
+pub struct ClassProto {
+    pub name: Rc<str>,
+    pub params: Vec<Rc<str>>,
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: Rc<str>,
+    pub params: RefCell<Vec<Rc<str>>>,
+    pub superclass: RefCell<Option<Rc<Class>>>,
+    pub methods: RefCell<HashMap<Rc<str>, Rc<Closure>>>,
+    /// Assigns the declared fields of the class, run for every new instance.
+    pub defaults: RefCell<Option<Rc<Closure>>>,
+}
+
+impl Class {
+    pub fn new(proto: &ClassProto) -> Self {
+        Class {
+            name: proto.name.clone(),
+            params: RefCell::new(proto.params.clone()),
+            superclass: RefCell::new(None),
+            methods: RefCell::new(HashMap::new()),
+            defaults: RefCell::new(None),
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Closure>> {
+        match self.methods.borrow().get(name) {
+            Some(m) => Some(m.clone()),
+            None => self.superclass.borrow().as_ref()?.find_method(name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<Rc<str>, Value>,
+}
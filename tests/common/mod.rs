@@ -1,6 +1,6 @@
 use assert_cmd::Command;
 
-const INTERPRETER: &'static str = "rlox";
+const INTERPRETER: &str = "rlox";
 
 pub fn setup() -> Command {
     Command::cargo_bin(INTERPRETER).unwrap()
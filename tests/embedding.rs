@@ -0,0 +1,132 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use rlox::{LoxError, Session, Value};
+
+/// A writer the test keeps a handle to after giving it to the session.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn eval_returns_last_expression() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("1 + 2").unwrap().as_num(), Some(3.0));
+    assert!(session.eval("var a = 1;").unwrap().is_nil());
+    assert_eq!(session.eval("a = 2; a * 10;").unwrap().as_num(), Some(20.0));
+}
+
+#[test]
+fn state_persists_between_evals() {
+    let mut session = Session::new();
+    session
+        .eval("var count = 0; fun bump() { count = count + 1; return count; }")
+        .unwrap();
+    session.eval("bump();").unwrap();
+    assert_eq!(session.eval("bump()").unwrap().as_num(), Some(2.0));
+    assert_eq!(
+        session.get_global("count").and_then(|v| v.as_num()),
+        Some(2.0)
+    );
+    assert_eq!(session.get_global("bump").unwrap().type_name(), "function");
+    assert!(session.get_global("missing").is_none());
+}
+
+#[test]
+fn host_globals_and_functions() {
+    let mut session = Session::new();
+    session.define_global("greeting", "hello".into());
+    session.define_global("loud", true.into());
+    session.define_function("shout", 1, |args| match args[0].as_str() {
+        Some(s) => Ok(Value::from(s.to_uppercase())),
+        None => Err("shout() expects a string".to_string()),
+    });
+    let value = session.eval("shout(greeting)").unwrap();
+    assert_eq!(value.as_str(), Some("HELLO"));
+    assert_eq!(session.eval("!loud").unwrap().as_bool(), Some(false));
+
+    let error = session.eval("shout(1)").unwrap_err();
+    assert!(matches!(error, LoxError::Runtime(..)));
+    assert!(error.to_string().contains("shout() expects a string"));
+    assert!(matches!(
+        session.eval("shout()"),
+        Err(LoxError::Runtime(..))
+    ));
+}
+
+#[test]
+fn print_goes_to_output() {
+    let output = Output::default();
+    let mut session = Session::new();
+    session.set_output(output.clone());
+    session
+        .eval("print \"a\"; for (var i = 0; i < 3; i = i + 1) print i;")
+        .unwrap();
+    assert_eq!(output.text(), "a\n0\n1\n2\n");
+}
+
+#[test]
+fn errors_carry_their_stage() {
+    let mut session = Session::new();
+    assert!(matches!(session.eval("\"open"), Err(LoxError::Scan(_))));
+    assert!(matches!(session.eval("var = 1;"), Err(LoxError::Parse(_))));
+    assert!(matches!(
+        session.eval("undefined()"),
+        Err(LoxError::Runtime(..))
+    ));
+    // the session is still usable afterwards
+    assert_eq!(session.eval("2").unwrap().as_num(), Some(2.0));
+}
+
+#[test]
+fn runtime_errors_carry_a_traceback() {
+    let mut session = Session::new();
+    session
+        .eval("fun inner() { return -\"x\"; }\nfun outer() { return inner(); }")
+        .unwrap();
+    let Err(LoxError::Runtime(_, traceback)) = session.eval("outer()") else {
+        panic!("expected a runtime error");
+    };
+    let calls: Vec<(&str, usize)> = traceback
+        .iter()
+        .map(|f| (f.function.as_str(), f.line))
+        .collect();
+    assert_eq!(calls, [("inner", 1), ("outer", 0)]);
+    // a later error does not inherit the old traceback
+    let Err(LoxError::Runtime(_, traceback)) = session.eval("-nil") else {
+        panic!("expected a runtime error");
+    };
+    assert!(traceback.is_empty());
+}
+
+#[test]
+fn error_locations_are_one_based() {
+    let mut session = Session::new();
+    let error = session
+        .eval("fun f() {\n  return -nil;\n}\nf();")
+        .unwrap_err();
+    let LoxError::Runtime(_, traceback) = &error else {
+        panic!("expected a runtime error, found {error}");
+    };
+    // `-` is the 10th character of its line, the call's `)` the 3rd
+    assert!(error.to_string().starts_with("[2:10] "));
+    assert_eq!(traceback[0].to_string(), "in f, called at line 4, column 3");
+}
@@ -2,18 +2,508 @@ use std::fs;
 
 mod common;
 
-const VAR: &'static str = "./tests/var/var.rlox";
-const VAR_RES: &'static str = "./tests/var/result";
-const VAR_SCOPE: &'static str = "./tests/scope/scope.rlox";
-const VAR_SCOPE_RES: &'static str = "./tests/scope/result";
-const FUN_DECL: &'static str = "./tests/fun/fun.rlox";
-const FUN_DECL_RES: &'static str = "./tests/fun/result";
-const EXPR: &'static str = "./tests/expressions/expressions.rlox";
-const EXPR_RES: &'static str = "./tests/expressions/result";
-const LOOPS: &'static str = "./tests/loops/loops.rlox";
-const LOOPS_RES: &'static str = "./tests/loops/result";
-const CLASS: &'static str = "./tests/class/class.rlox";
-const CLASS_RES: &'static str = "./tests/class/result";
+const VAR: &str = "./tests/var/var.rlox";
+const VAR_RES: &str = "./tests/var/result";
+const VAR_SCOPE: &str = "./tests/scope/scope.rlox";
+const VAR_SCOPE_RES: &str = "./tests/scope/result";
+const FUN_DECL: &str = "./tests/fun/fun.rlox";
+const FUN_DECL_RES: &str = "./tests/fun/result";
+const EXPR: &str = "./tests/expressions/expressions.rlox";
+const EXPR_RES: &str = "./tests/expressions/result";
+const LOOPS: &str = "./tests/loops/loops.rlox";
+const LOOPS_RES: &str = "./tests/loops/result";
+const CLASS: &str = "./tests/class/class.rlox";
+const CLASS_RES: &str = "./tests/class/result";
+const INHERITANCE: &str = "./tests/inheritance/inheritance.rlox";
+const INHERITANCE_RES: &str = "./tests/inheritance/result";
+const INHERIT_NON_CLASS: &str = "./tests/inheritance/non_class.rlox";
+const INHERIT_SELF: &str = "./tests/inheritance/self.rlox";
+const CLOSURES: &str = "./tests/closures/closures.rlox";
+const CLOSURES_RES: &str = "./tests/closures/result";
+const SLOTS: &str = "./tests/slots/slots.rlox";
+const SLOTS_RES: &str = "./tests/slots/result";
+const CONTROL: &str = "./tests/control/control.rlox";
+const CONTROL_RES: &str = "./tests/control/result";
+const CALLEES: &str = "./tests/callees/callees.rlox";
+const CALLEES_RES: &str = "./tests/callees/result";
+const ESCAPES: &str = "./tests/escapes/escapes.rlox";
+const ESCAPES_RES: &str = "./tests/escapes/result";
+const NUMBERS: &str = "./tests/numbers/numbers.rlox";
+const NUMBERS_RES: &str = "./tests/numbers/result";
+const BOUND_METHODS: &str = "./tests/bound_methods/bound_methods.rlox";
+const BOUND_METHODS_RES: &str = "./tests/bound_methods/result";
+const NATIVES: &str = "./tests/natives/natives.rlox";
+const NATIVES_INPUT: &str = "./tests/natives/input";
+const NATIVES_RES: &str = "./tests/natives/result";
+const NATIVE_ERRORS: [(&str, &str, &str); 3] = [
+    (
+        "./tests/natives/bad_arg.rlox",
+        "len() expects a string, a list or a map, found number",
+        "1   | print len(1);",
+    ),
+    (
+        "./tests/natives/arity.rlox",
+        "this function takes 0 args but 1 were given",
+        "1   | print clock(1);",
+    ),
+    (
+        "./tests/natives/not_a_trait.rlox",
+        "implements() expects a trait, found class",
+        "2   | print implements(A(), A);",
+    ),
+];
+const TYPE_ERRORS: [(&str, &str, &str); 14] = [
+    (
+        "./tests/type_errors/sub_string.rlox",
+        "operands of '-' must be numbers, found string and number",
+        "1   | print \"a\" - 1;",
+    ),
+    (
+        "./tests/type_errors/mul_bool.rlox",
+        "operands of '*' must be numbers, found bool and number",
+        "1   | print true * 2;",
+    ),
+    (
+        "./tests/type_errors/negate_string.rlox",
+        "operand of '-' must be a number, found string",
+        "2   | print -x;",
+    ),
+    (
+        "./tests/type_errors/add_mixed.rlox",
+        "operands of '+' must be two numbers or two strings, found string and number",
+        "1   | print \"a\" + 1;",
+    ),
+    (
+        "./tests/type_errors/compare_nil.rlox",
+        "operands of '<' must be numbers, found nil and number",
+        "1   | print nil < 1;",
+    ),
+    (
+        "./tests/type_errors/divide_zero.rlox",
+        "division by zero",
+        "1   | print 1 / 0;",
+    ),
+    (
+        "./tests/type_errors/divide_zero_fun.rlox",
+        "division by zero",
+        "2   |   return n / (n - n);",
+    ),
+    (
+        "./tests/callees/not_callable.rlox",
+        "can only call functions and classes, found string",
+        "2   | notAFunction(1);",
+    ),
+    (
+        "./tests/callees/arity.rlox",
+        "this function takes 3 args but 2 were given",
+        "4   | print args(1, 2);",
+    ),
+    (
+        "./tests/callees/call_result.rlox",
+        "can only call functions and classes, found number",
+        "4   | one()();",
+    ),
+    (
+        "./tests/class_fields/too_many_args.rlox",
+        "Point takes 2 args but 3 were given",
+        "2   | var p = Point(1, 2, 3);",
+    ),
+    (
+        "./tests/class_fields/too_few_args.rlox",
+        "Point takes 2 args but 1 were given",
+        "2   | var p = Point(1);",
+    ),
+    (
+        "./tests/class_fields/undeclared_field.rlox",
+        "undefined property",
+        "3   | b.size = 1;",
+    ),
+    (
+        "./tests/operators/missing_method.rlox",
+        "operands of '+' must be two numbers or two strings, found instance and instance",
+        "2   | print Point(1, 2) + Point(3, 4);",
+    ),
+];
+const TRACEBACK: &str = "./tests/traceback/traceback.rlox";
+const TRACEBACK_RES: &str = "./tests/traceback/result";
+const TRACEBACK_ERR: &str = "\x1b[37;41m Error \x1b[0m: \
+operands of '+' must be two numbers or two strings, found number and string
+traceback, innermost call first:
+    in check, called at line 3, column 39
+    in withdraw, called at line 16, column 17
+    in pay, called at line 19, column 16
+";
+const OVERFLOW: &str = "./tests/traceback/overflow.rlox";
+const LISTS: &str = "./tests/lists/lists.rlox";
+const LISTS_RES: &str = "./tests/lists/result";
+const LIST_ERRORS: [(&str, &str, &str); 6] = [
+    (
+        "./tests/lists/out_of_bounds.rlox",
+        "list index 2 out of bounds for length 2",
+        "2   | print xs[2];",
+    ),
+    (
+        "./tests/lists/negative_index.rlox",
+        "list index -1 out of bounds for length 2",
+        "2   | xs[-1] = 0;",
+    ),
+    (
+        "./tests/lists/fractional_index.rlox",
+        "list index must be a whole number, found 0.5",
+        "1   | print [1][0.5];",
+    ),
+    (
+        "./tests/lists/pop_empty.rlox",
+        "pop from empty list",
+        "2   | xs.pop();",
+    ),
+    (
+        "./tests/lists/not_a_list.rlox",
+        "only lists and maps can be indexed, found number",
+        "2   | print n[0];",
+    ),
+    (
+        "./tests/lists/insert_out_of_bounds.rlox",
+        "list index 2 out of bounds for length 1",
+        "2   | xs.insert(2, 1);",
+    ),
+];
+const MAPS: &str = "./tests/maps/maps.rlox";
+const MAPS_RES: &str = "./tests/maps/result";
+const MAP_ERRORS: [(&str, &str, &str); 3] = [
+    (
+        "./tests/maps/missing_key.rlox",
+        "undefined map key \"alan\"",
+        "2   | print ages[\"alan\"];",
+    ),
+    (
+        "./tests/maps/bad_key.rlox",
+        "map keys must be strings, numbers, booleans or nil, found list",
+        "2   | m[[1]] = 2;",
+    ),
+    (
+        "./tests/maps/unknown_method.rlox",
+        "undefined map method size",
+        "2   | print m.size();",
+    ),
+];
+const FOR_IN: &str = "./tests/for_in/for_in.rlox";
+const FOR_IN_RES: &str = "./tests/for_in/result";
+const FOR_IN_ERRORS: [(&str, &str, &str); 2] = [
+    (
+        "./tests/for_in/not_iterable.rlox",
+        "can't iterate over a number",
+        "1   | for (var x in 42) {",
+    ),
+    (
+        "./tests/for_in/no_iter.rlox",
+        "Box instance can't be iterated, it has no iter",
+        "3   | for (var x in Box(1)) print x;",
+    ),
+];
+const EXCEPTIONS: &str = "./tests/exceptions/exceptions.rlox";
+const EXCEPTIONS_RES: &str = "./tests/exceptions/result";
+const EXCEPTION_ERRORS: [(&str, &str, &str); 3] = [
+    (
+        "./tests/exceptions/uncaught.rlox",
+        "uncaught exception: oops",
+        "2   |   throw \"oops\";",
+    ),
+    (
+        "./tests/exceptions/missing_catch.rlox",
+        "expected \"catch\" or \"finally\" after try block",
+        "4   | print 2;",
+    ),
+    (
+        "./tests/exceptions/user_error.rlox",
+        "uncaught exception: Error instance",
+        "3   | throw Error(\"not built in\");",
+    ),
+];
+const LAMBDAS: &str = "./tests/lambdas/lambdas.rlox";
+const LAMBDAS_RES: &str = "./tests/lambdas/result";
+const CLASS_FIELDS: &str = "./tests/class_fields/class_fields.rlox";
+const CLASS_FIELDS_RES: &str = "./tests/class_fields/result";
+const CLASS_FIELDS_VM: &str = "./tests/class_fields/vm.rlox";
+const CLASS_FIELDS_VM_RES: &str = "./tests/class_fields/vm_result";
+const CLASS_FIELD_ERRORS: [(&str, &str, &str); 4] = [
+    (
+        "./tests/class_fields/init_arity.rlox",
+        "this function takes 1 args but 0 were given",
+        "8   | var c = Circle();",
+    ),
+    (
+        "./tests/class_fields/unknown_token.rlox",
+        "expected a method or a field in class body, found print",
+        "2   |   print 1;",
+    ),
+    (
+        "./tests/class_fields/init_return.rlox",
+        "can't return a value from an initializer [return]",
+        "3   |     return 1;",
+    ),
+    (
+        "./tests/class_fields/init_and_params.rlox",
+        "a class with an init method can't have parameters",
+        "1   | class Point(x) {",
+    ),
+];
+const STATICS: &str = "./tests/statics/statics.rlox";
+const STATICS_RES: &str = "./tests/statics/result";
+const STATIC_ERRORS: [(&str, &str, &str); 3] = [
+    (
+        "./tests/statics/undefined_member.rlox",
+        "undefined static member origin",
+        "2   | print Point.origin;",
+    ),
+    (
+        "./tests/statics/undefined_static_field.rlox",
+        "undefined static field total",
+        "4   | Point.total = 1;",
+    ),
+    (
+        "./tests/statics/bad_static.rlox",
+        "expected a method or a field after \"static\", found print",
+        "2   |   static print 1;",
+    ),
+];
+const ACCESSORS: &str = "./tests/accessors/accessors.rlox";
+const ACCESSORS_RES: &str = "./tests/accessors/result";
+const ACCESSOR_ERRORS: [(&str, &str, &str); 4] = [
+    (
+        "./tests/accessors/getter_only.rlox",
+        "can't assign to area, it only has a getter",
+        "7   | c.area = 2;",
+    ),
+    (
+        "./tests/accessors/setter_only.rlox",
+        "can't read area, it only has a setter",
+        "7   | print c.area;",
+    ),
+    (
+        "./tests/accessors/setter_params.rlox",
+        "a setter must take exactly one parameter",
+        "2   |   set area(a, b) {",
+    ),
+    (
+        "./tests/accessors/getter_parens.rlox",
+        "expected \"{\" after getter name",
+        "2   |   get area() {",
+    ),
+];
+const OPERATORS: &str = "./tests/operators/operators.rlox";
+const OPERATORS_RES: &str = "./tests/operators/result";
+const OPERATOR_ERRORS: [(&str, &str, &str); 5] = [
+    (
+        "./tests/operators/str_not_string.rlox",
+        "__str__ must return a string, found number",
+        "2   |   fun __str__() {",
+    ),
+    (
+        "./tests/operators/str_error.rlox",
+        "operands of '+' must be two numbers or two strings, found number and string",
+        "3   |     return this.x + \",\" + this.y;",
+    ),
+    (
+        "./tests/operators/str_thrown.rlox",
+        "uncaught exception: failure 3",
+        "6   | throw Failure(3);",
+    ),
+    (
+        "./tests/operators/str_error_thrown.rlox",
+        "operands of '+' must be two numbers or two strings, found string and number",
+        "3   |     return \"failure \" + this.code;",
+    ),
+    (
+        "./tests/operators/str_not_iterable.rlox",
+        "box of 1 can't be iterated, it has no iter",
+        "6   | for (var x in Box(1)) print x;",
+    ),
+];
+const TRAITS: &str = "./tests/traits/traits.rlox";
+const TRAITS_RES: &str = "./tests/traits/result";
+const TRAIT_ERRORS: [(&str, &str, &str); 5] = [
+    (
+        "./tests/traits/missing_method.rlox",
+        "Silent must implement greet() required by Greeter",
+        "5   | class Silent with Greeter {}",
+    ),
+    (
+        "./tests/traits/not_a_trait.rlox",
+        "Base is not a trait",
+        "3   | class Derived with Base {}",
+    ),
+    (
+        "./tests/traits/trait_super.rlox",
+        "can't use 'super' in a trait",
+        "3   |     return super.shout();",
+    ),
+    (
+        "./tests/traits/trait_body.rlox",
+        "expected a method in trait body, found var",
+        "2   |   var name = \"x\";",
+    ),
+    (
+        "./tests/traits/implements_arg.rlox",
+        "implements() expects a trait, found class",
+        "3   | print implements(Base(), Base);",
+    ),
+];
+const MODULES: &str = "./tests/modules/modules.rlox";
+const MODULES_RES: &str = "./tests/modules/result";
+const MODULE_ERRORS: [(&str, &str, &str); 5] = [
+    (
+        "./tests/modules/cycle_a.rlox",
+        "in \"cycle_b.rlox\" at line 2: import cycle: cycle_a.rlox -> cycle_b.rlox -> cycle_a.rlox",
+        "1   | import \"cycle_b.rlox\" as b;",
+    ),
+    (
+        "./tests/modules/missing_file.rlox",
+        "can't read module \"lib/missing.rlox\": No such file or directory (os error 2)",
+        "1   | import \"lib/missing.rlox\" as missing;",
+    ),
+    (
+        "./tests/modules/missing_export.rlox",
+        "module \"lib/shapes.rlox\" has no export Circle",
+        "1   | from \"lib/shapes.rlox\" import Circle;",
+    ),
+    (
+        "./tests/modules/module_error.rlox",
+        "in \"lib/broken.rlox\" at line 3: operands of '+' must be two numbers or two strings, found number and nil",
+        "2   | import \"lib/broken.rlox\" as broken;",
+    ),
+    (
+        "./tests/modules/nested_import.rlox",
+        "imports are only allowed at the top level [import]",
+        "2   |   import \"lib/counter.rlox\" as counter;",
+    ),
+];
+const ESCAPE_ERRORS: [(&str, &str, &str); 4] = [
+    (
+        "./tests/escapes/bad_escape.rlox",
+        "invalid escape sequence \\q",
+        "2   | print \"bad \\q escape\";",
+    ),
+    (
+        "./tests/escapes/bad_code_point.rlox",
+        "invalid unicode code point 110000",
+        "1   | print \"\\u{110000}\";",
+    ),
+    (
+        "./tests/escapes/bad_unicode.rlox",
+        "invalid character 'x' in unicode escape",
+        "1   | print \"\\u{12x4}\";",
+    ),
+    (
+        "./tests/escapes/unicode_brace.rlox",
+        "expected \"{\" after \\u",
+        "1   | print \"\\u2603\";",
+    ),
+];
+const NUMBER_ERRORS: [(&str, &str, &str); 4] = [
+    (
+        "./tests/numbers/empty_hex.rlox",
+        "expected hexadecimal digits after 0x",
+        "1   | var max = 0x;",
+    ),
+    (
+        "./tests/numbers/empty_exponent.rlox",
+        "expected digits in exponent",
+        "1   | var small = 1e;",
+    ),
+    (
+        "./tests/numbers/trailing_separator.rlox",
+        "a digit separator must be between two digits",
+        "1   | var million = 1_000_;",
+    ),
+    (
+        "./tests/numbers/bad_digit.rlox",
+        "invalid digit 2 in binary literal",
+        "1   | var flags = 0b102;",
+    ),
+];
+const INTERPOLATION: &str = "./tests/interpolation/interpolation.rlox";
+const INTERPOLATION_RES: &str = "./tests/interpolation/result";
+const REPL: &str = "./tests/repl/input";
+const REPL_RES: &str = "./tests/repl/result";
+const REPL_STR: &str = "./tests/repl/str_input";
+const REPL_STR_RES: &str = "./tests/repl/str_result";
+const VM_BACKEND: &str = "--backend=vm";
+/// One program per feature the vm backend rejects at compile time.
+const VM_UNSUPPORTED: [(&str, &str, &str); 11] = [
+    (
+        LISTS,
+        "lists are not supported by the vm backend",
+        "1   | var xs = [1, 2, 3];",
+    ),
+    (
+        MAPS,
+        "maps are not supported by the vm backend",
+        "1   | var ages = {\"ada\": 36, \"alan\": 41,};",
+    ),
+    (
+        FOR_IN,
+        "for-in loops are not supported by the vm backend",
+        "1   | for (var x in [1, 2, 3]) {",
+    ),
+    (
+        EXCEPTIONS,
+        "exceptions are not supported by the vm backend",
+        "1   | try {",
+    ),
+    (
+        STATICS,
+        "static members are not supported by the vm backend",
+        "2   |   static var created = 0;",
+    ),
+    (
+        ACCESSORS,
+        "getters and setters are not supported by the vm backend",
+        "10  |   get area {",
+    ),
+    (
+        OPERATORS,
+        "operator overloading methods are not supported by the vm backend",
+        "2   |   fun __add__(other) {",
+    ),
+    (
+        TRAITS,
+        "traits are not supported by the vm backend",
+        "1   | trait Shape {",
+    ),
+    (
+        MODULES,
+        "imports are not supported by the vm backend",
+        "1   | import \"lib/shapes.rlox\" as shapes;",
+    ),
+    (
+        INTERPOLATION,
+        "interpolated strings are not supported by the vm backend",
+        "3   | print \"Hello ${name}, you are ${age + 1}\";",
+    ),
+    (
+        "./tests/lambdas/vm.rlox",
+        "anonymous functions are not supported by the vm backend",
+        "1   | var f = (x) => x;",
+    ),
+];
+
+const PROGRAMS: [(&str, &str); 14] = [
+    (VAR, VAR_RES),
+    (VAR_SCOPE, VAR_SCOPE_RES),
+    (FUN_DECL, FUN_DECL_RES),
+    (EXPR, EXPR_RES),
+    (LOOPS, LOOPS_RES),
+    (CLASS, CLASS_RES),
+    (INHERITANCE, INHERITANCE_RES),
+    (CLOSURES, CLOSURES_RES),
+    (SLOTS, SLOTS_RES),
+    (CONTROL, CONTROL_RES),
+    (CALLEES, CALLEES_RES),
+    (ESCAPES, ESCAPES_RES),
+    (NUMBERS, NUMBERS_RES),
+    (BOUND_METHODS, BOUND_METHODS_RES),
+];
 
 #[test]
 fn test_var_declarations() {
@@ -56,3 +546,398 @@ fn test_class() {
     let mut cmd = common::setup();
     cmd.arg(CLASS).assert().success().stdout(res);
 }
+
+#[test]
+fn test_inheritance() {
+    let res = fs::read_to_string(INHERITANCE_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(INHERITANCE).assert().success().stdout(res);
+}
+
+#[test]
+fn test_inheritance_errors() {
+    let mut cmd = common::setup();
+    cmd.arg(INHERIT_NON_CLASS).assert().failure();
+    let mut cmd = common::setup();
+    cmd.arg(INHERIT_SELF).assert().failure();
+}
+
+#[test]
+fn test_closures() {
+    let res = fs::read_to_string(CLOSURES_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(CLOSURES).assert().success().stdout(res);
+}
+
+#[test]
+fn test_slots() {
+    let res = fs::read_to_string(SLOTS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(SLOTS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_control_flow() {
+    let res = fs::read_to_string(CONTROL_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(CONTROL).assert().success().stdout(res);
+}
+
+#[test]
+fn test_callees() {
+    let res = fs::read_to_string(CALLEES_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(CALLEES).assert().success().stdout(res);
+}
+
+#[test]
+fn test_bound_methods() {
+    let res = fs::read_to_string(BOUND_METHODS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(BOUND_METHODS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_vm_backend() {
+    for (program, result) in PROGRAMS {
+        let res = fs::read_to_string(result).unwrap();
+        let mut cmd = common::setup();
+        cmd.arg(VM_BACKEND)
+            .arg(program)
+            .assert()
+            .success()
+            .stdout(res);
+    }
+}
+
+#[test]
+fn test_vm_unsupported() {
+    for (program, message, line) in VM_UNSUPPORTED {
+        assert_error(VM_BACKEND, program, message, line);
+    }
+}
+
+#[test]
+fn test_vm_inheritance_errors() {
+    for program in [INHERIT_NON_CLASS, INHERIT_SELF] {
+        let mut cmd = common::setup();
+        cmd.arg(VM_BACKEND).arg(program).assert().failure();
+    }
+}
+
+#[test]
+fn test_repl_session() {
+    let input = fs::read_to_string(REPL).unwrap();
+    let res = fs::read_to_string(REPL_RES).unwrap();
+    for backend in ["--backend=tree", VM_BACKEND] {
+        let mut cmd = common::setup();
+        cmd.arg(backend)
+            .write_stdin(input.clone())
+            .assert()
+            .success()
+            .stdout(res.clone());
+    }
+}
+
+#[test]
+fn test_repl_echo_runs_str() {
+    let input = fs::read_to_string(REPL_STR).unwrap();
+    let res = fs::read_to_string(REPL_STR_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg("--backend=tree")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(res);
+}
+
+#[test]
+fn test_natives() {
+    let input = fs::read_to_string(NATIVES_INPUT).unwrap();
+    let res = fs::read_to_string(NATIVES_RES).unwrap();
+    for backend in ["--backend=tree", VM_BACKEND] {
+        let mut cmd = common::setup();
+        cmd.arg(backend)
+            .arg(NATIVES)
+            .write_stdin(input.clone())
+            .assert()
+            .success()
+            .stdout(res.clone());
+    }
+}
+
+#[test]
+fn test_natives_errors() {
+    for backend in ["--backend=tree", VM_BACKEND] {
+        for (program, message, line) in NATIVE_ERRORS {
+            assert_error(backend, program, message, line);
+        }
+    }
+}
+
+/// Runs `program` and checks that it fails with `message`, highlighting `line`.
+fn assert_error(backend: &str, program: &str, message: &str, line: &str) {
+    let mut cmd = common::setup();
+    let assert = cmd.arg(backend).arg(program).assert().code(0x41);
+    let output = assert.get_output();
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    let error = format!("\x1b[37;41m Error \x1b[0m: {message}\n");
+    assert!(stderr.starts_with(&error), "{program}: {stderr}");
+    assert!(stdout.contains(line), "{program}: {stdout}");
+}
+
+#[test]
+fn test_type_errors() {
+    for backend in ["--backend=tree", VM_BACKEND] {
+        for (program, message, line) in TYPE_ERRORS {
+            assert_error(backend, program, message, line);
+        }
+    }
+}
+
+#[test]
+fn test_traceback() {
+    let res = fs::read_to_string(TRACEBACK_RES).unwrap();
+    for backend in ["--backend=tree", VM_BACKEND] {
+        let mut cmd = common::setup();
+        cmd.arg(backend)
+            .arg(TRACEBACK)
+            .assert()
+            .code(0x41)
+            .stdout(res.clone())
+            .stderr(TRACEBACK_ERR);
+    }
+}
+
+#[test]
+fn test_stack_overflow() {
+    for backend in ["--backend=tree", VM_BACKEND] {
+        assert_error(
+            backend,
+            OVERFLOW,
+            "stack overflow",
+            "6   |           var x = 1 + (2 * (3 + this.go(n + 1)));",
+        );
+        let mut cmd = common::setup();
+        let assert = cmd.arg(backend).arg(OVERFLOW).assert().code(0x41);
+        let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+        let traceback: Vec<&str> = stderr.lines().skip(1).collect();
+        assert_eq!(traceback.len(), 4, "{stderr}");
+        assert_eq!(traceback[1], "    in go, called at line 6, column 38");
+        assert!(traceback[2].starts_with("    ... 102"), "{stderr}");
+        assert_eq!(traceback[3], "    in go, called at line 13, column 11");
+    }
+}
+
+#[test]
+fn test_unreadable_file() {
+    let mut cmd = common::setup();
+    let assert = cmd.arg("./tests/missing.rlox").assert().code(0x42);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.starts_with("Error reading file: "), "{stderr}");
+}
+
+#[test]
+fn test_lists() {
+    let res = fs::read_to_string(LISTS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(LISTS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_list_errors() {
+    for (program, message, line) in LIST_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_maps() {
+    let res = fs::read_to_string(MAPS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(MAPS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_map_errors() {
+    for (program, message, line) in MAP_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_for_in() {
+    let res = fs::read_to_string(FOR_IN_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(FOR_IN).assert().success().stdout(res);
+}
+
+#[test]
+fn test_for_in_errors() {
+    for (program, message, line) in FOR_IN_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_exceptions() {
+    let res = fs::read_to_string(EXCEPTIONS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(EXCEPTIONS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_exception_errors() {
+    for (program, message, line) in EXCEPTION_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_class_fields() {
+    let res = fs::read_to_string(CLASS_FIELDS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(CLASS_FIELDS).assert().success().stdout(res);
+
+    let res = fs::read_to_string(CLASS_FIELDS_VM_RES).unwrap();
+    for backend in ["--backend=tree", VM_BACKEND] {
+        let mut cmd = common::setup();
+        cmd.arg(backend)
+            .arg(CLASS_FIELDS_VM)
+            .assert()
+            .success()
+            .stdout(res.clone());
+    }
+}
+
+#[test]
+fn test_class_field_errors() {
+    for backend in ["--backend=tree", VM_BACKEND] {
+        for (program, message, line) in CLASS_FIELD_ERRORS {
+            assert_error(backend, program, message, line);
+        }
+    }
+}
+
+#[test]
+fn test_statics() {
+    let res = fs::read_to_string(STATICS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(STATICS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_static_errors() {
+    for (program, message, line) in STATIC_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_accessors() {
+    let res = fs::read_to_string(ACCESSORS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(ACCESSORS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_accessor_errors() {
+    for (program, message, line) in ACCESSOR_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_operators() {
+    let res = fs::read_to_string(OPERATORS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(OPERATORS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_operator_errors() {
+    for (program, message, line) in OPERATOR_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_traits() {
+    let res = fs::read_to_string(TRAITS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(TRAITS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_trait_errors() {
+    for (program, message, line) in TRAIT_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_modules() {
+    let res = fs::read_to_string(MODULES_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(MODULES).assert().success().stdout(res);
+}
+
+#[test]
+fn test_module_errors() {
+    for (program, message, line) in MODULE_ERRORS {
+        assert_error("--backend=tree", program, message, line);
+    }
+}
+
+#[test]
+fn test_escapes() {
+    let res = fs::read_to_string(ESCAPES_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(ESCAPES).assert().success().stdout(res);
+}
+
+#[test]
+fn test_escape_errors() {
+    for backend in ["--backend=tree", VM_BACKEND] {
+        for (program, message, line) in ESCAPE_ERRORS {
+            assert_error(backend, program, message, line);
+        }
+    }
+}
+
+#[test]
+fn test_numbers() {
+    let res = fs::read_to_string(NUMBERS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(NUMBERS).assert().success().stdout(res);
+}
+
+#[test]
+fn test_number_errors() {
+    for backend in ["--backend=tree", VM_BACKEND] {
+        for (program, message, line) in NUMBER_ERRORS {
+            assert_error(backend, program, message, line);
+        }
+    }
+}
+
+#[test]
+fn test_interpolation() {
+    let res = fs::read_to_string(INTERPOLATION_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(INTERPOLATION).assert().success().stdout(res);
+    assert_error(
+        "--backend=tree",
+        "./tests/interpolation/unclosed.rlox",
+        "expected \"}\" after interpolated expression",
+        "2   | print \"a ${a b}\";",
+    );
+}
+
+#[test]
+fn test_lambdas() {
+    let res = fs::read_to_string(LAMBDAS_RES).unwrap();
+    let mut cmd = common::setup();
+    cmd.arg(LAMBDAS).assert().success().stdout(res);
+}